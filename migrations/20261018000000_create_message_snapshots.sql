@@ -0,0 +1,9 @@
+-- 添付ファイル付きメッセージと、スナップショットチャンネルへ転送したメッセージの対応
+CREATE TABLE message_snapshots (
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    snapshot_message_id BIGINT NOT NULL,
+    PRIMARY KEY (channel_id, message_id)
+);
+
+CREATE INDEX message_snapshots_snapshot_message_id_idx ON message_snapshots (snapshot_message_id);
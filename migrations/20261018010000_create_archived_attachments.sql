@@ -0,0 +1,13 @@
+-- ローカルに保存した添付ファイル (実体は content_hash をファイル名として保存される)
+CREATE TABLE archived_attachments (
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    attachment_id BIGINT NOT NULL,
+    filename TEXT NOT NULL,
+    content_type TEXT,
+    size BIGINT NOT NULL,
+    content_hash TEXT NOT NULL,
+    PRIMARY KEY (channel_id, message_id, attachment_id)
+);
+
+CREATE INDEX archived_attachments_content_hash_idx ON archived_attachments (content_hash);
@@ -0,0 +1,6 @@
+-- 過去メッセージのキャッシュでチャンネルごとに取得済みの最新メッセージ
+CREATE TABLE message_cache_progress (
+    channel_id BIGINT PRIMARY KEY,
+    newest_message_id BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,8 @@
+-- チャンネルのオーナーからピン留めの権限を委任されたユーザー
+CREATE TABLE pin_delegates (
+    channel_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    granted_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (channel_id, user_id)
+);
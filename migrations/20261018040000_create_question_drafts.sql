@@ -0,0 +1,6 @@
+-- 質問フォームの入力途中の内容 (ユーザーごとに1件)
+CREATE TABLE question_drafts (
+    user_id BIGINT PRIMARY KEY,
+    data TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
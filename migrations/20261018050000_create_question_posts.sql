@@ -0,0 +1,8 @@
+-- 投稿された質問のフォームの入力内容 (質問の編集に使用)
+CREATE TABLE question_posts (
+    thread_id BIGINT PRIMARY KEY,
+    author_id BIGINT NOT NULL,
+    data TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
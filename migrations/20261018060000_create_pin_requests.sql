@@ -0,0 +1,22 @@
+-- ピン留めの権限がないメンバーからのピン留めのリクエスト
+CREATE TABLE pin_requests (
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    requester_id BIGINT NOT NULL,
+    -- 承認・却下したユーザー (未処理の場合は NULL)
+    resolved_by BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (channel_id, message_id, requester_id)
+);
+
+-- リクエストの承認・却下のボタンを表示したメッセージ (DM の場合はオーナーごとに送信される)
+CREATE TABLE pin_request_prompts (
+    prompt_channel_id BIGINT NOT NULL,
+    prompt_message_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    requester_id BIGINT NOT NULL,
+    PRIMARY KEY (prompt_channel_id, prompt_message_id),
+    FOREIGN KEY (channel_id, message_id, requester_id)
+        REFERENCES pin_requests (channel_id, message_id, requester_id) ON DELETE CASCADE
+);
@@ -1,18 +1,19 @@
 use std::{
+    borrow::Cow,
     collections::{HashMap, HashSet},
-    path::Path,
+    path::{Path, PathBuf},
     time::Duration as StdDuration,
 };
 
-use anyhow::Context as _;
+use anyhow::{Context as _, bail, ensure};
 use chrono::Duration;
-use duration_str::{deserialize_duration, deserialize_duration_chrono};
+use duration_str::{deserialize_duration, deserialize_duration_chrono, deserialize_option_duration_chrono};
 use regex::Regex;
 use serde::{Deserialize, Deserializer};
 use serde_with::{DisplayFromStr, serde_as};
 use serenity::{
-    all::{ChannelId, ForumTagId, GuildId, RoleId, Token, UserId},
-    model::id::GenericChannelId,
+    all::{ChannelId, ForumTagId, GuildId, RoleId, Token, UserId, WebhookId},
+    model::{channel::ReactionType, id::GenericChannelId},
 };
 use tokio::fs::read_to_string;
 
@@ -25,6 +26,9 @@ pub struct AppConfig {
     pub auto_kick: AutoKickConfig,
     pub honeypot: HoneypotConfig,
     pub message_logging: MessageLoggingConfig,
+    /// 省略時はチャンネルのログを記録しない
+    #[serde(default)]
+    pub channel_logging: Option<ChannelLoggingConfig>,
     pub message_cache: MessageCacheConfig,
     pub pin: PinConfig,
     pub thread_auto_invite: ThreadAutoInviteConfig,
@@ -50,6 +54,7 @@ pub struct BotConfig {
 #[derive(Debug, Deserialize)]
 pub struct AuthConfig {
     pub log_channel_id: ChannelId,
+    pub log_webhook: Option<LogWebhookConfig>,
     pub role_id: RoleId,
     pub keyword: String,
     pub dummy_keywords: Vec<String>,
@@ -70,12 +75,209 @@ pub struct HoneypotConfig {
     pub message_lookback: Duration,
     pub kick_message: String,
     pub log_channel_id: ChannelId,
+    pub log_webhook: Option<LogWebhookConfig>,
+}
+
+impl AuthConfig {
+    pub fn log_destination(&self) -> LogDestination<'_> {
+        LogDestination {
+            channel_id: self.log_channel_id,
+            webhook: self.log_webhook.as_ref(),
+        }
+    }
+}
+
+impl HoneypotConfig {
+    pub fn log_destination(&self) -> LogDestination<'_> {
+        LogDestination {
+            channel_id: self.log_channel_id,
+            webhook: self.log_webhook.as_ref(),
+        }
+    }
+}
+
+/**
+ログの送信先
+
+`webhook` が設定されている場合は Webhook で送信し、失敗した場合は `channel_id` に Bot として送信します。
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct LogDestination<'a> {
+    pub channel_id: ChannelId,
+    pub webhook: Option<&'a LogWebhookConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogWebhookConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl LogWebhookConfig {
+    /**
+    URL (`https://discord.com/api/webhooks/{id}/{token}`) から Webhook の ID とトークンを取り出します。
+
+    `?thread_id=` などのクエリ文字列やフラグメントは無視します。
+    */
+    pub fn id_and_token(&self) -> Option<(WebhookId, &str)> {
+        let path = self.url.split(['?', '#']).next()?;
+        let mut segments = path.trim_end_matches('/').rsplit('/');
+        let token = segments.next()?;
+        let id = segments.next()?.parse().ok()?;
+
+        Some((WebhookId::new(id), token))
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct MessageLoggingConfig {
     pub channel_id: ChannelId,
+    pub webhook: Option<LogWebhookConfig>,
     pub snapshot_channel_id: GenericChannelId,
+    #[serde(default)]
+    pub routes: Vec<MessageLogRoute>,
+    #[serde(default)]
+    pub optional_kinds: Vec<MessageLogRouteKind>,
+    pub snapshot_retention: Option<SnapshotRetentionConfig>,
+    #[serde(default)]
+    pub snapshot_backend: SnapshotBackend,
+    #[serde(default)]
+    pub guild_snapshot_backends: HashMap<GuildId, SnapshotBackend>,
+    pub local_archive: Option<LocalArchiveConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotBackend {
+    /// スナップショットチャンネルへの転送
+    #[default]
+    Forward,
+    /// ローカルディレクトリへの保存
+    Local,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LocalArchiveConfig {
+    pub directory: PathBuf,
+    pub max_file_size: u64,
+    pub max_total_size: u64,
+    #[serde(default)]
+    pub max_file_size_by_content_type: HashMap<String, u64>,
+}
+
+impl LocalArchiveConfig {
+    /**
+    コンテンツタイプに前方一致する設定のうち最も長いものと、全体の上限の小さい方を返します。
+    */
+    pub fn max_file_size_for(&self, content_type: Option<&str>) -> u64 {
+        let content_type = content_type.unwrap_or_default();
+
+        self.max_file_size_by_content_type
+            .iter()
+            .filter(|(prefix, _)| content_type.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.max_file_size, |(_, size)| (*size).min(self.max_file_size))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotRetentionConfig {
+    #[serde(default, deserialize_with = "deserialize_option_duration_chrono")]
+    pub max_age: Option<Duration>,
+    pub max_count_per_channel: Option<u32>,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub prune_interval: StdDuration,
+}
+
+impl MessageLoggingConfig {
+    /**
+    ログの送信元チャンネル (自身から親・カテゴリの順) と種類から送信先を決定します。
+
+    送信元に近いチャンネルを指定したルートが優先され、`sources` が空のルートは最後に評価されます。
+    いずれにも一致しない場合は `channel_id` と `webhook` を返します。
+    */
+    pub fn log_destination(&self, source_ids: &[GenericChannelId], kind: MessageLogRouteKind) -> LogDestination<'_> {
+        let routes = || self.routes.iter().filter(move |route| route.matches_kind(kind));
+
+        source_ids
+            .iter()
+            .find_map(|id| routes().find(|route| route.sources.contains(id)))
+            .or_else(|| routes().find(|route| route.sources.is_empty()))
+            .map_or(
+                LogDestination {
+                    channel_id: self.channel_id,
+                    webhook: self.webhook.as_ref(),
+                },
+                |route| LogDestination {
+                    channel_id: route.channel_id,
+                    webhook: route.webhook.as_ref(),
+                },
+            )
+    }
+
+    /**
+    ギルドで使用するスナップショットの保存方式を返します。
+
+    `local_archive` が設定されていない場合は常に転送を使用します。
+    */
+    pub fn snapshot_backend(&self, guild_id: Option<GuildId>) -> SnapshotBackend {
+        let backend = guild_id
+            .and_then(|id| self.guild_snapshot_backends.get(&id).copied())
+            .unwrap_or(self.snapshot_backend);
+
+        match backend {
+            SnapshotBackend::Local if self.local_archive.is_none() => SnapshotBackend::Forward,
+            backend => backend,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessageLogRoute {
+    pub channel_id: ChannelId,
+    pub webhook: Option<LogWebhookConfig>,
+    #[serde(default)]
+    pub sources: Vec<GenericChannelId>,
+    #[serde(default)]
+    pub kinds: Vec<MessageLogRouteKind>,
+}
+
+impl MessageLogRoute {
+    fn matches_kind(&self, kind: MessageLogRouteKind) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&kind)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageLogRouteKind {
+    Edit,
+    Delete,
+    BulkDelete,
+    EmbedRemoved,
+    EmbedsSuppressed,
+    StickerRemoved,
+    PollChanged,
+    Pin,
+    ReactionsCleared,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelLoggingConfig {
+    pub channel_id: ChannelId,
+    /// ギルドごとのログの送信先 (省略したギルドは `channel_id` に送信)
+    #[serde(default)]
+    pub guild_channel_ids: HashMap<GuildId, ChannelId>,
+}
+
+impl ChannelLoggingConfig {
+    pub fn channel_id_for(&self, guild_id: GuildId) -> ChannelId {
+        self.guild_channel_ids
+            .get(&guild_id)
+            .copied()
+            .unwrap_or(self.channel_id)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -86,28 +288,201 @@ pub struct MessageCacheConfig {
     pub request_window: StdDuration,
     pub requests_per_window: u8,
     pub concurrent_channels: u8,
+    #[serde(default = "default_recent_window", deserialize_with = "deserialize_duration_chrono")]
+    pub recent_window: Duration,
+    #[serde(default = "default_max_messages_per_channel")]
+    pub max_messages_per_channel: usize,
+    #[serde(default, deserialize_with = "deserialize_option_duration_chrono")]
+    pub max_message_age: Option<Duration>,
+    #[serde(default = "default_eviction_interval", deserialize_with = "deserialize_duration")]
+    pub eviction_interval: StdDuration,
+    #[serde(default)]
+    pub include_channel_ids: Vec<GenericChannelId>,
+    #[serde(default)]
+    pub exclude_channel_ids: Vec<GenericChannelId>,
+    #[serde(default, deserialize_with = "deserialize_option_duration_chrono")]
+    pub archived_thread_max_age: Option<Duration>,
+    pub forum_thread_limit: Option<usize>,
+    #[serde(default)]
+    pub forum_thread_limits: HashMap<ChannelId, usize>,
+}
+
+fn default_recent_window() -> Duration {
+    Duration::hours(24)
 }
 
+fn default_max_messages_per_channel() -> usize {
+    5000
+}
+
+fn default_eviction_interval() -> StdDuration {
+    StdDuration::from_secs(60 * 60)
+}
+
+impl MessageCacheConfig {
+    /**
+    チャンネル (自身から親・カテゴリの順) が過去メッセージの取得対象かどうかを判定します。
+
+    `exclude_channel_ids` が優先され、`include_channel_ids` が空の場合は全てのチャンネルが対象です。
+    */
+    pub fn is_backfill_target(&self, ancestor_ids: &[GenericChannelId]) -> bool {
+        if ancestor_ids.iter().any(|id| self.exclude_channel_ids.contains(id)) {
+            return false;
+        }
+
+        self.include_channel_ids.is_empty() || ancestor_ids.iter().any(|id| self.include_channel_ids.contains(id))
+    }
+
+    /**
+    フォーラムのアーカイブされたスレッドを取得する上限を返します。
+    */
+    pub fn forum_thread_limit_for(&self, forum_id: ChannelId) -> Option<usize> {
+        self.forum_thread_limits
+            .get(&forum_id)
+            .copied()
+            .or(self.forum_thread_limit)
+    }
+}
+
+#[serde_as]
 #[derive(Debug, Deserialize)]
 pub struct PinConfig {
     #[serde(deserialize_with = "to_pin_channels")]
-    pub channels: HashMap<ChannelId, UserId>,
+    pub channels: HashMap<GenericChannelId, PinChannelOwners>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub reaction_emoji: Option<ReactionType>,
+    pub reaction_threshold: Option<usize>,
+    #[serde(default)]
+    pub reaction_threshold_channel_ids: Vec<GenericChannelId>,
+    #[serde(default)]
+    pub limit_behavior: PinLimitBehavior,
+    #[serde(default)]
+    pub channel_limit_behaviors: HashMap<GenericChannelId, PinLimitBehavior>,
+    #[serde(default)]
+    pub request_destination: PinRequestDestination,
+    pub log_channel_id: Option<ChannelId>,
+    pub log_webhook: Option<LogWebhookConfig>,
+}
+
+/**
+ピン留めのリクエストの送信先
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinRequestDestination {
+    /// オーナーをメンションしてチャンネルに送信する (リクエストは他のメンバーにも表示される)
+    Channel,
+    /// オーナーにDMで送信する
+    #[default]
+    Dm,
+}
+
+/**
+ピン留めの上限に達した場合の動作
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinLimitBehavior {
+    /// 置き換えるピン留めを選択させる
+    #[default]
+    Select,
+    /// 最も古いピン留めを解除する
+    UnpinOldest,
+}
+
+impl PinConfig {
+    /**
+    チャンネル (自身から親・カテゴリの順) に設定されたオーナーを全てまとめて返します。
+    */
+    pub fn owners_for(&self, ancestor_ids: &[GenericChannelId]) -> PinChannelOwners {
+        let mut owners = PinChannelOwners::default();
+        for entry in ancestor_ids.iter().filter_map(|id| self.channels.get(id)) {
+            for user_id in &entry.user_ids {
+                if !owners.user_ids.contains(user_id) {
+                    owners.user_ids.push(*user_id);
+                }
+            }
+            for role_id in &entry.role_ids {
+                if !owners.role_ids.contains(role_id) {
+                    owners.role_ids.push(*role_id);
+                }
+            }
+        }
+
+        owners
+    }
+
+    /**
+    チャンネル (自身から親・カテゴリの順) に設定されたピン留めの上限に達した場合の動作を返します。
+    */
+    pub fn limit_behavior_for(&self, ancestor_ids: &[GenericChannelId]) -> PinLimitBehavior {
+        ancestor_ids
+            .iter()
+            .find_map(|id| self.channel_limit_behaviors.get(id).copied())
+            .unwrap_or(self.limit_behavior)
+    }
+
+    /**
+    チャンネル (自身から親・カテゴリの順) で、認証済みメンバーのリアクション数によるピン留めが有効な場合はその閾値を返します。
+    */
+    pub fn reaction_threshold_for(&self, ancestor_ids: &[GenericChannelId]) -> Option<usize> {
+        self.reaction_threshold.filter(|_| {
+            ancestor_ids
+                .iter()
+                .any(|id| self.reaction_threshold_channel_ids.contains(id))
+        })
+    }
+
+    pub fn log_destination(&self) -> Option<LogDestination<'_>> {
+        self.log_channel_id.map(|channel_id| LogDestination {
+            channel_id,
+            webhook: self.log_webhook.as_ref(),
+        })
+    }
+}
+
+/**
+チャンネル・スレッド・カテゴリに設定されたピン留めのオーナー
+
+カテゴリに設定した場合は、その中の全てのチャンネルに適用されます。
+*/
+#[derive(Debug, Default, Clone)]
+pub struct PinChannelOwners {
+    pub user_ids: Vec<UserId>,
+    pub role_ids: Vec<RoleId>,
 }
 
-fn to_pin_channels<'de, D>(deserializer: D) -> Result<HashMap<ChannelId, UserId>, D::Error>
+fn to_pin_channels<'de, D>(deserializer: D) -> Result<HashMap<GenericChannelId, PinChannelOwners>, D::Error>
 where
     D: Deserializer<'de>,
 {
+    /// `owner` は単一のオーナーを指定する従来の形式
     #[derive(Debug, Deserialize)]
     struct Temp {
-        id: ChannelId,
-        owner: UserId,
+        id: GenericChannelId,
+        owner: Option<UserId>,
+        #[serde(default)]
+        owners: Vec<UserId>,
+        #[serde(default)]
+        owner_roles: Vec<RoleId>,
+    }
+
+    let mut channels = HashMap::<GenericChannelId, PinChannelOwners>::new();
+    for i in Vec::<Temp>::deserialize(deserializer)? {
+        if i.owner.is_none() && i.owners.is_empty() && i.owner_roles.is_empty() {
+            return Err(serde::de::Error::custom(format!(
+                "pin channel {} must have one of owner, owners or owner_roles",
+                i.id
+            )));
+        }
+
+        let owners = channels.entry(i.id).or_default();
+        owners.user_ids.extend(i.owner.into_iter().chain(i.owners));
+        owners.role_ids.extend(i.owner_roles);
     }
 
-    Ok(Vec::deserialize(deserializer)?
-        .into_iter()
-        .map(|i: Temp| (i.id, i.owner))
-        .collect())
+    Ok(channels)
 }
 
 #[derive(Debug, Deserialize)]
@@ -118,8 +493,485 @@ pub struct ThreadAutoInviteConfig {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(try_from = "RawQuestionConfig")]
 pub struct QuestionConfig {
+    /// 質問フォーラムの一覧 (先頭が `/question` の既定の投稿先)
+    pub forums: Vec<QuestionForumConfig>,
+    pub templates: HashMap<String, QuestionTemplate>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuestionForumConfig {
+    /// `/question` の選択肢に表示する名前
+    pub name: String,
     pub forum_id: ChannelId,
     pub solved_tag: ForumTagId,
     pub solved_name_prefix: String,
+    /// 使用するフォームのテンプレート名 (省略時は組み込みのテンプレート)
+    pub template: Option<String>,
+}
+
+/**
+`[question]` に直接フォーラムを設定する従来の形式も受け付けるための中間表現
+*/
+#[derive(Deserialize)]
+struct RawQuestionConfig {
+    #[serde(default)]
+    forums: Vec<QuestionForumConfig>,
+    #[serde(default)]
+    templates: HashMap<String, QuestionTemplate>,
+    forum_id: Option<ChannelId>,
+    solved_tag: Option<ForumTagId>,
+    #[serde(default)]
+    solved_name_prefix: String,
+    template: Option<String>,
+}
+
+impl TryFrom<RawQuestionConfig> for QuestionConfig {
+    type Error = AppError;
+
+    fn try_from(raw: RawQuestionConfig) -> Result<Self, Self::Error> {
+        let mut forums = raw.forums;
+        match (raw.forum_id, raw.solved_tag) {
+            (Some(forum_id), Some(solved_tag)) => forums.insert(
+                0,
+                QuestionForumConfig {
+                    name: "質問".to_owned(),
+                    forum_id,
+                    solved_tag,
+                    solved_name_prefix: raw.solved_name_prefix,
+                    template: raw.template,
+                },
+            ),
+            (None, None) => {}
+            _ => bail!("Both forum_id and solved_tag are required for [question]"),
+        }
+        ensure!(!forums.is_empty(), "At least one question forum is required");
+
+        for (name, template) in &raw.templates {
+            template
+                .validate()
+                .with_context(|| format!("Invalid question template: {name}"))?;
+        }
+        for forum in &forums {
+            if let Some(name) = &forum.template {
+                ensure!(
+                    raw.templates.contains_key(name),
+                    "Unknown question template `{name}` for forum {}",
+                    forum.forum_id
+                );
+            }
+        }
+
+        Ok(Self {
+            forums,
+            templates: raw.templates,
+        })
+    }
+}
+
+impl QuestionConfig {
+    /**
+    フォーラムのIDから質問フォーラムの設定を返します。
+    */
+    pub fn forum(&self, forum_id: ChannelId) -> Option<&QuestionForumConfig> {
+        self.forums.iter().find(|forum| forum.forum_id == forum_id)
+    }
+
+    /**
+    フォーラムのIDから質問フォーラムの設定を返します。未指定または見つからない場合は先頭のフォーラムを返します。
+    */
+    pub fn forum_or_default(&self, forum_id: Option<ChannelId>) -> Option<&QuestionForumConfig> {
+        forum_id
+            .and_then(|forum_id| self.forum(forum_id))
+            .or_else(|| self.forums.first())
+    }
+
+    /**
+    フォーラムで使用するフォームのテンプレートを返します。未指定の場合は組み込みのテンプレートを返します。
+    */
+    pub fn template(&self, forum: &QuestionForumConfig) -> Cow<'_, QuestionTemplate> {
+        match &forum.template {
+            // テンプレートが存在することは設定の読み込み時に確認している
+            Some(name) => Cow::Borrowed(&self.templates[name]),
+            None => Cow::Owned(QuestionTemplate::default()),
+        }
+    }
+}
+
+/**
+質問フォームのテンプレート
+
+各モーダルは入力ボタンに対応し、`output` の `{フィールドID}` が入力内容に置き換えられて初期メッセージになります。
+*/
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuestionTemplate {
+    /// スレッドのタイトルに使用するフィールドのID
+    pub title_field: String,
+    pub modals: Vec<QuestionModalTemplate>,
+    pub output: String,
+}
+
+/// 1つのモーダルに配置できるフィールドの最大数
+const MAX_MODAL_FIELDS: usize = 5;
+
+/// フォームに配置できるモーダルの入力ボタンの最大数 (タグの選択で1行、送信ボタンと合わせて残りの4行に5つずつ)
+const MAX_MODALS: usize = 4 * 5 - 1;
+
+/// モーダルのタイトルとフィールドのラベルの最大文字数
+const MAX_MODAL_LABEL_LENGTH: usize = 45;
+
+/// ボタンのラベルの最大文字数
+const MAX_BUTTON_LABEL_LENGTH: usize = 80;
+
+/// スレッド名の最大文字数
+const MAX_THREAD_NAME_LENGTH: u16 = 100;
+
+impl QuestionTemplate {
+    /**
+    テンプレートが Discord のモーダル・コンポーネントの制限を満たしているかを確認します。
+    */
+    fn validate(&self) -> Result<(), AppError> {
+        ensure!(!self.modals.is_empty(), "No modals");
+        ensure!(
+            self.modals.len() <= MAX_MODALS,
+            "Too many modals: {} (max {MAX_MODALS})",
+            self.modals.len()
+        );
+
+        let mut field_ids = HashSet::new();
+        for modal in &self.modals {
+            ensure!(
+                !modal.fields.is_empty() && modal.fields.len() <= MAX_MODAL_FIELDS,
+                "Modal `{}` must have 1 to {MAX_MODAL_FIELDS} fields",
+                modal.title
+            );
+            ensure!(
+                modal.title.chars().count() <= MAX_MODAL_LABEL_LENGTH,
+                "Modal title `{}` is longer than {MAX_MODAL_LABEL_LENGTH} characters",
+                modal.title
+            );
+            ensure!(
+                modal.button_label.chars().count() <= MAX_BUTTON_LABEL_LENGTH,
+                "Button label `{}` is longer than {MAX_BUTTON_LABEL_LENGTH} characters",
+                modal.button_label
+            );
+
+            for field in &modal.fields {
+                ensure!(field_ids.insert(&field.id), "Duplicate field id `{}`", field.id);
+                ensure!(
+                    field.label.chars().count() <= MAX_MODAL_LABEL_LENGTH,
+                    "Field label `{}` is longer than {MAX_MODAL_LABEL_LENGTH} characters",
+                    field.label
+                );
+            }
+        }
+
+        let Some(title_field) = self
+            .modals
+            .iter()
+            .flat_map(|modal| &modal.fields)
+            .find(|field| field.id == self.title_field)
+        else {
+            bail!("Title field `{}` does not exist", self.title_field);
+        };
+        ensure!(
+            title_field.required,
+            "Title field `{}` must be required",
+            title_field.id
+        );
+        ensure!(
+            title_field
+                .max_length
+                .is_some_and(|max_length| max_length <= MAX_THREAD_NAME_LENGTH),
+            "Title field `{}` must have max_length of {MAX_THREAD_NAME_LENGTH} or less",
+            title_field.id
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuestionModalTemplate {
+    pub title: String,
+    pub button_label: String,
+    pub fields: Vec<QuestionFieldTemplate>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuestionFieldTemplate {
+    pub id: String,
+    pub label: String,
+    pub placeholder: Option<String>,
+    pub min_length: Option<u16>,
+    pub max_length: Option<u16>,
+    #[serde(default = "default_true")]
+    pub required: bool,
+    #[serde(default)]
+    pub paragraph: bool,
+    /// 未入力の場合にモーダルに表示する初期値
+    pub default: Option<String>,
+    /// 任意のフィールドが空の場合に出力する文字列
+    #[serde(default = "default_empty_text")]
+    pub empty_text: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_empty_text() -> String {
+    "なし".to_owned()
+}
+
+impl QuestionFieldTemplate {
+    fn new(id: &str, label: &str, placeholder: &str, min_length: Option<u16>, max_length: u16) -> Self {
+        Self {
+            id: id.to_owned(),
+            label: label.to_owned(),
+            placeholder: Some(placeholder.to_owned()),
+            min_length,
+            max_length: Some(max_length),
+            required: true,
+            paragraph: false,
+            default: None,
+            empty_text: default_empty_text(),
+        }
+    }
+
+    fn paragraph(self, default: Option<&str>) -> Self {
+        Self {
+            paragraph: true,
+            default: default.map(str::to_owned),
+            ..self
+        }
+    }
+}
+
+impl Default for QuestionTemplate {
+    fn default() -> Self {
+        Self {
+            title_field: "title".to_owned(),
+            modals: vec![
+                QuestionModalTemplate {
+                    title: "質問の基本情報を入力".to_owned(),
+                    button_label: "質問の基本情報を入力".to_owned(),
+                    fields: vec![
+                        QuestionFieldTemplate::new(
+                            "title",
+                            "質問のタイトル (わかりやすいように質問内容を要約してください)",
+                            "質問のタイトルを入力してください",
+                            Some(10),
+                            100,
+                        ),
+                        QuestionFieldTemplate::new(
+                            "mc_version",
+                            "Minecraftのバージョン",
+                            "Minecraftのバージョンを入力してください",
+                            Some(3),
+                            20,
+                        ),
+                        QuestionFieldTemplate::new(
+                            "loader",
+                            "Modローダー (Forge, Fabric, NeoForge, Quilt, その他)",
+                            "使用しているModローダーを選択してください",
+                            Some(3),
+                            20,
+                        ),
+                    ],
+                },
+                QuestionModalTemplate {
+                    title: "質問の詳細情報を入力".to_owned(),
+                    button_label: "質問の詳細情報を入力".to_owned(),
+                    fields: vec![
+                        QuestionFieldTemplate::new(
+                            "content",
+                            "質問の内容 (詳細な質問内容を入力してください)",
+                            "質問の内容を入力してください",
+                            Some(20),
+                            1000,
+                        )
+                        .paragraph(Some("例:クラッシュした, 変な挙動をする, modの扱い方がわからない")),
+                        QuestionFieldTemplate::new(
+                            "goal",
+                            "問題解決の達成基準",
+                            "問題解決の達成基準を入力してください",
+                            Some(20),
+                            1000,
+                        )
+                        .paragraph(Some(
+                            "例: クラッシュから抜け出したい, このような挙動にしたい, このmodでこのようなことがしたい",
+                        )),
+                        QuestionFieldTemplate::new(
+                            "tried",
+                            "試したこと・調べたこと",
+                            "質問を行う前に試したことや調べたことを入力してください",
+                            Some(20),
+                            1000,
+                        )
+                        .paragraph(Some(
+                            "例:○○というサイトに掲載されてた対処法を試した\nAIに聞いてみてこのような回答を得られた\nなど",
+                        )),
+                        QuestionFieldTemplate {
+                            required: false,
+                            ..QuestionFieldTemplate::new(
+                                "logs",
+                                "mclo.gs にアップロードしたログやクラッシュレポートなどのリンク (任意)",
+                                "mclo.gs にアップロードしたログやクラッシュレポートなどのリンクを入力してください",
+                                None,
+                                1000,
+                            )
+                            .paragraph(None)
+                        },
+                    ],
+                },
+            ],
+            output: "### 基本情報\n- Minecraftバージョン: {mc_version}\n- Modローダー: {loader}\n### 質問内容\n- 質問内容:\n{content}\n- 問題解決の達成基準:\n{goal}\n- 試したこと・調べたこと:\n{tried}\n- ログやクラッシュレポートのリンク:\n{logs}".to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_question_template_is_valid() {
+        QuestionTemplate::default().validate().unwrap();
+    }
+
+    #[test]
+    fn question_template_rejects_invalid_title_field() {
+        let mut template = QuestionTemplate::default();
+        template.title_field = "missing".to_owned();
+        assert!(template.validate().is_err());
+
+        let mut template = QuestionTemplate::default();
+        template.modals[0].fields[0].max_length = Some(200);
+        assert!(template.validate().is_err());
+
+        let mut template = QuestionTemplate::default();
+        template.modals[0].fields[0].required = false;
+        assert!(template.validate().is_err());
+    }
+
+    #[test]
+    fn question_template_rejects_duplicate_field_ids() {
+        let mut template = QuestionTemplate::default();
+        let field = template.modals[0].fields[0].clone();
+        template.modals[1].fields.push(field);
+        assert!(template.validate().is_err());
+    }
+
+    #[test]
+    fn question_config_rejects_unknown_template() {
+        let config = toml::from_str::<QuestionConfig>(
+            r#"
+            [[forums]]
+            name = "質問"
+            forum_id = 1
+            solved_tag = 2
+            solved_name_prefix = "[解決済み] "
+            template = "missing"
+            "#,
+        );
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn question_config_accepts_legacy_flat_section() {
+        let config = toml::from_str::<QuestionConfig>(
+            r#"
+            forum_id = 1
+            solved_tag = 2
+            solved_name_prefix = "[解決済み] "
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.forums.len(), 1);
+        let forum = &config.forums[0];
+        assert_eq!(forum.forum_id, ChannelId::new(1));
+        assert_eq!(forum.solved_tag, ForumTagId::new(2));
+        assert_eq!(forum.solved_name_prefix, "[解決済み] ");
+        assert!(forum.template.is_none());
+    }
+
+    #[test]
+    fn question_config_puts_legacy_forum_first() {
+        let config = toml::from_str::<QuestionConfig>(
+            r#"
+            forum_id = 1
+            solved_tag = 2
+
+            [[forums]]
+            name = "Mod開発"
+            forum_id = 3
+            solved_tag = 4
+            solved_name_prefix = ""
+            "#,
+        )
+        .unwrap();
+
+        let forum_ids = config.forums.iter().map(|forum| forum.forum_id).collect::<Vec<_>>();
+        assert_eq!(forum_ids, [ChannelId::new(1), ChannelId::new(3)]);
+        assert_eq!(config.forum_or_default(None).unwrap().forum_id, ChannelId::new(1));
+    }
+
+    #[test]
+    fn question_config_rejects_incomplete_or_empty_sections() {
+        assert!(toml::from_str::<QuestionConfig>("forum_id = 1").is_err());
+        assert!(toml::from_str::<QuestionConfig>("solved_tag = 2").is_err());
+        assert!(toml::from_str::<QuestionConfig>("").is_err());
+    }
+
+    fn pin_config(channels: &str) -> Result<PinConfig, toml::de::Error> {
+        toml::from_str(&format!("channels = {channels}"))
+    }
+
+    #[test]
+    fn pin_config_accepts_legacy_single_owner() {
+        let config = pin_config("[{ id = 1, owner = 10 }]").unwrap();
+
+        let owners = &config.channels[&GenericChannelId::new(1)];
+        assert_eq!(owners.user_ids, [UserId::new(10)]);
+        assert!(owners.role_ids.is_empty());
+    }
+
+    #[test]
+    fn pin_config_merges_owners_of_same_channel() {
+        let config = pin_config(
+            "[{ id = 1, owner = 10, owners = [11] }, { id = 1, owner_roles = [20] }, { id = 2, owner_roles = [21] }]",
+        )
+        .unwrap();
+
+        let owners = &config.channels[&GenericChannelId::new(1)];
+        assert_eq!(owners.user_ids, [UserId::new(10), UserId::new(11)]);
+        assert_eq!(owners.role_ids, [RoleId::new(20)]);
+        assert_eq!(config.channels[&GenericChannelId::new(2)].role_ids, [RoleId::new(21)]);
+    }
+
+    #[test]
+    fn pin_config_rejects_channel_without_owners() {
+        assert!(pin_config("[{ id = 1 }]").is_err());
+        assert!(pin_config("[{ id = 1, owners = [], owner_roles = [] }]").is_err());
+    }
+
+    #[test]
+    fn pin_owners_for_collects_ancestors_without_duplicates() {
+        let config = pin_config(
+            "[{ id = 1, owners = [10, 11] }, { id = 2, owner = 11, owner_roles = [20] }, { id = 3, owner = 12 }]",
+        )
+        .unwrap();
+
+        // スレッド (1) → チャンネル (4、未設定) → カテゴリ (2) の順
+        let ids = [1, 4, 2].map(GenericChannelId::new);
+        let owners = config.owners_for(&ids);
+        assert_eq!(owners.user_ids, [UserId::new(10), UserId::new(11)]);
+        assert_eq!(owners.role_ids, [RoleId::new(20)]);
+
+        let owners = config.owners_for(&[GenericChannelId::new(5)]);
+        assert!(owners.user_ids.is_empty() && owners.role_ids.is_empty());
+    }
 }
@@ -1,21 +1,39 @@
-use std::sync::Arc;
+use std::{
+    any::{Any, TypeId, type_name},
+    collections::HashMap,
+    sync::Arc,
+};
 
 use poise::ApplicationContext;
 use serenity::all::prelude::Context;
+use sqlx::PgPool;
 use tokio::sync::RwLock;
 
-use crate::app::{AppApplicationContext, AppContext, AppError, config::AppConfig};
+use crate::app::{AppApplicationContext, AppContext, AppError, BotError, config::AppConfig};
 
 pub struct BotData {
     config: RwLock<Arc<AppConfig>>,
+    database: PgPool,
+    /// 各機能で共有する状態 (型ごとに1つ)
+    extensions: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
 }
 
 impl BotData {
-    pub fn new(config: AppConfig) -> Self {
+    pub fn new(config: AppConfig, database: PgPool) -> Self {
         Self {
             config: RwLock::new(Arc::new(config)),
+            database,
+            extensions: HashMap::new(),
         }
     }
+
+    /**
+    各機能で共有する状態を登録します。同じ型の状態が登録済みの場合は置き換えます。
+    */
+    pub fn with_extension<T: Send + Sync + 'static>(mut self, extension: Arc<T>) -> Self {
+        self.extensions.insert(TypeId::of::<T>(), extension);
+        self
+    }
 }
 
 pub trait BotDataExt {
@@ -29,6 +47,22 @@ pub trait BotDataExt {
         let data = self.bot_data();
         *data.config.write().await = Arc::new(config);
     }
+
+    fn database(&self) -> PgPool {
+        self.bot_data().database.clone()
+    }
+
+    /**
+    `BotData::with_extension` で登録された状態を取得します。登録されていない場合はエラーを返します。
+    */
+    fn extension<T: Send + Sync + 'static>(&self) -> Result<Arc<T>, BotError> {
+        self.bot_data()
+            .extensions
+            .get(&TypeId::of::<T>())
+            .cloned()
+            .and_then(|extension| extension.downcast::<T>().ok())
+            .ok_or(BotError::MissingExtension(type_name::<T>()))
+    }
 }
 
 impl BotDataExt for Context {
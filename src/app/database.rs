@@ -0,0 +1,43 @@
+use std::env;
+
+use anyhow::Context as _;
+use sqlx::{
+    PgPool,
+    postgres::{PgConnectOptions, PgPoolOptions},
+};
+
+use crate::app::AppError;
+
+fn env_var(key: &str) -> Result<String, AppError> {
+    env::var(key).with_context(|| format!("Environment variable is not set: {key}"))
+}
+
+/**
+環境変数の接続情報からデータベースへ接続し、マイグレーションを適用します。
+*/
+pub async fn connect_database() -> Result<PgPool, AppError> {
+    let port = match env::var("DB_PORT") {
+        Ok(port) => port.parse().context("Failed to parse DB_PORT")?,
+        Err(_) => 5432,
+    };
+
+    let options = PgConnectOptions::new()
+        .host(&env_var("DB_HOST")?)
+        .port(port)
+        .database(&env_var("DB_NAME")?)
+        .username(&env_var("DB_USER")?)
+        .password(&env_var("POSTGRES_PASSWORD")?);
+
+    let pool = PgPoolOptions::new()
+        .max_connections(5)
+        .connect_with(options)
+        .await
+        .context("Failed to connect to database")?;
+
+    sqlx::migrate!()
+        .run(&pool)
+        .await
+        .context("Failed to run database migrations")?;
+
+    Ok(pool)
+}
@@ -23,6 +23,8 @@ pub enum BotError {
     CacheMiss { resource: &'static str, id: String },
     #[error("付与可能な招待用ロールがありません (メンバー数上限: {member_limit})")]
     NoAvailableInviteRole { member_limit: u32 },
+    #[error("共有する状態が登録されていません: {0}")]
+    MissingExtension(&'static str),
 }
 
 pub async fn on_error(error: FrameworkError<'_, BotData, AppError>) {
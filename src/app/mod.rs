@@ -2,12 +2,14 @@
 
 pub mod config;
 mod data;
+mod database;
 mod error;
 mod event_handler;
 pub mod types;
 pub mod utils;
 
 pub use data::{BotData, BotDataExt};
+pub use database::connect_database;
 pub use error::{BotError, on_error};
 pub use event_handler::{MainEventHandler, handle_event_error};
 pub use types::{AppApplicationContext, AppCommand, AppContext, AppError};
@@ -0,0 +1,192 @@
+use std::borrow::Cow;
+
+use anyhow::{Context as _, anyhow};
+use serenity::{
+    all::{Context, prelude::CacheHttp},
+    builder::{
+        CreateAttachment, CreateComponent, CreateEmbed, CreateMessage, EditAttachments, EditMessage,
+        EditWebhookMessage, ExecuteWebhook,
+    },
+    model::channel::{Message, MessageFlags},
+};
+use tracing::warn;
+
+use crate::{
+    app::{
+        AppError,
+        config::{LogDestination, LogWebhookConfig},
+    },
+    utils::{create_safe_allowed_mentions, create_safe_message, send_message},
+};
+
+/**
+Bot と Webhook のどちらでも送信できるログメッセージ
+*/
+#[derive(Clone)]
+pub struct LogMessage<'a> {
+    content: Option<Cow<'a, str>>,
+    embeds: Vec<CreateEmbed<'a>>,
+    components: Vec<CreateComponent<'a>>,
+    files: Vec<CreateAttachment<'a>>,
+}
+
+impl<'a> LogMessage<'a> {
+    fn new() -> Self {
+        Self {
+            content: None,
+            embeds: Vec::new(),
+            components: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    pub fn content(content: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            content: Some(content.into()),
+            ..Self::new()
+        }
+    }
+
+    pub fn embed(embed: CreateEmbed<'a>) -> Self {
+        Self {
+            embeds: vec![embed],
+            ..Self::new()
+        }
+    }
+
+    pub fn components_v2(components: Vec<CreateComponent<'a>>) -> Self {
+        Self {
+            components,
+            ..Self::new()
+        }
+    }
+
+    pub fn add_file(mut self, file: CreateAttachment<'a>) -> Self {
+        self.files.push(file);
+        self
+    }
+
+    fn into_create_message(self) -> CreateMessage<'a> {
+        let mut message = create_safe_message().embeds(self.embeds).files(self.files);
+
+        if let Some(content) = self.content {
+            message = message.content(content);
+        }
+        if !self.components.is_empty() {
+            message = message
+                .components(self.components)
+                .flags(MessageFlags::IS_COMPONENTS_V2);
+        }
+
+        message
+    }
+
+    fn into_execute_webhook(self, webhook: &'a LogWebhookConfig) -> ExecuteWebhook<'a> {
+        let mut message = ExecuteWebhook::new()
+            .allowed_mentions(create_safe_allowed_mentions())
+            .embeds(self.embeds)
+            .files(self.files);
+
+        if let Some(content) = self.content {
+            message = message.content(content);
+        }
+        if !self.components.is_empty() {
+            message = message
+                .components(self.components)
+                .flags(MessageFlags::IS_COMPONENTS_V2)
+                .with_components(true);
+        }
+        if let Some(username) = &webhook.username {
+            message = message.username(username.as_str());
+        }
+        if let Some(avatar_url) = &webhook.avatar_url {
+            message = message.avatar_url(avatar_url.as_str());
+        }
+
+        message
+    }
+}
+
+async fn execute_webhook(
+    ctx: &Context,
+    webhook: &LogWebhookConfig,
+    message: LogMessage<'_>,
+) -> Result<Message, AppError> {
+    let (webhook_id, token) = webhook.id_and_token().ok_or_else(|| anyhow!("Invalid webhook URL"))?;
+
+    message
+        .into_execute_webhook(webhook)
+        .execute(ctx.http(), webhook_id, token, true)
+        .await?
+        .ok_or_else(|| anyhow!("Webhook did not return the sent message"))
+}
+
+/**
+ログメッセージを送信先に送信します。
+
+Webhook での送信に失敗した場合は、送信先のチャンネルに Bot として送信します。
+*/
+pub async fn send_log_message(
+    ctx: &Context,
+    destination: LogDestination<'_>,
+    message: LogMessage<'_>,
+) -> Result<Message, AppError> {
+    if let Some(webhook) = destination.webhook {
+        match execute_webhook(ctx, webhook, message.clone()).await {
+            Ok(message) => return Ok(message),
+            Err(error) => warn!("Failed to send log via webhook, falling back to bot: {error:#}"),
+        }
+    }
+
+    send_message(ctx, &destination.channel_id, message.into_create_message())
+        .await
+        .context("Failed to send log message")
+}
+
+/**
+`send_log_message` で送信したログメッセージのコンポーネントと添付ファイルを置き換えます。
+
+Webhook で送信されたメッセージは同じ Webhook で編集します。
+*/
+pub async fn edit_log_message<'a>(
+    ctx: &Context,
+    destination: LogDestination<'_>,
+    log_message: &mut Message,
+    components: Vec<CreateComponent<'a>>,
+    files: Vec<CreateAttachment<'a>>,
+) -> Result<(), AppError> {
+    let webhook = destination
+        .webhook
+        .and_then(|webhook| webhook.id_and_token())
+        .filter(|(webhook_id, _)| log_message.webhook_id == Some(*webhook_id));
+
+    if let Some((webhook_id, token)) = webhook {
+        let mut edit = EditWebhookMessage::new()
+            .allowed_mentions(create_safe_allowed_mentions())
+            .components(components)
+            .clear_attachments();
+        for file in files {
+            edit = edit.new_attachment(file);
+        }
+
+        *log_message = edit.execute(ctx.http(), log_message.id, webhook_id, token).await?;
+        return Ok(());
+    }
+
+    let mut attachments = EditAttachments::new();
+    for file in files {
+        attachments = attachments.add(file);
+    }
+
+    log_message
+        .edit(
+            &ctx,
+            EditMessage::new()
+                .allowed_mentions(create_safe_allowed_mentions())
+                .components(components)
+                .attachments(attachments),
+        )
+        .await?;
+
+    Ok(())
+}
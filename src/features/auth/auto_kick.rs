@@ -13,10 +13,10 @@ use serenity::{
 use tracing::error;
 
 use crate::{
-    app::{AppError, BotDataExt, config::AppConfig},
+    app::{AppError, BotDataExt, config::AppConfig, utils::log_message::send_log_message},
     core::BotEventHandler,
     features::auth::utils::create_auth_log_message,
-    utils::{create_message, send_message, stream_members},
+    utils::{create_message, stream_members},
 };
 
 pub struct AutoKickEventHandler {
@@ -55,9 +55,9 @@ impl AutoKickEventHandler {
             .await
             .context("Failed to auto-kick member")?;
 
-        send_message(
+        send_log_message(
             ctx,
-            &config.auth.log_channel_id,
+            config.auth.log_destination(),
             create_auth_log_message("認証期限切れのため Kick", Color::ORANGE, member, Some(dm_succeeded)),
         )
         .await
@@ -4,10 +4,11 @@ use std::{
     time::{Duration, Instant},
 };
 
+use crate::app::utils::log_message::{LogMessage, send_log_message};
 use crate::app::{AppContext, AppError, BotDataExt, BotError};
 use crate::core::BotEventHandler;
 use crate::features::auth::utils::create_auth_log_message;
-use crate::utils::{create_ephemeral_message, create_interaction_message, create_message, create_model, send_message};
+use crate::utils::{create_ephemeral_message, create_interaction_message, create_message, create_model};
 use anyhow::Context as _;
 use dashmap::DashMap;
 use poise::say_reply;
@@ -167,17 +168,17 @@ impl KeywordAuthEventHandler {
         }
 
         if let Err(error) = member.add_role(ctx.http(), config.role_id, Some("認証成功")).await {
-            let log = create_message(format!(
+            let log = LogMessage::content(format!(
                 "{} にロールを追加できませんでした。\n```\n{error:#}```",
                 member.mention()
             ));
-            let _ = send_message(ctx, &config.log_channel_id, log).await;
+            let _ = send_log_message(ctx, config.log_destination(), log).await;
             return Err(error).context("Failed to grant authentication role");
         }
 
-        send_message(
+        send_log_message(
             ctx,
-            &config.log_channel_id,
+            config.log_destination(),
             create_auth_log_message("認証成功", branding::GREEN, member, None),
         )
         .await
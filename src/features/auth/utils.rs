@@ -1,19 +1,19 @@
 use std::borrow::Cow;
 
 use serenity::all::{Mentionable, MessageBuilder};
-use serenity::builder::{CreateEmbed, CreateMessage};
+use serenity::builder::CreateEmbed;
 use serenity::model::Color;
 use serenity::model::guild::Member;
 use serenity::utils::EmbedMessageBuilding;
 
-use crate::utils::create_safe_message;
+use crate::app::utils::log_message::LogMessage;
 
 pub(in crate::features::auth) fn create_auth_log_message<'a>(
     title: impl Into<Cow<'a, str>>,
     color: impl Into<Color>,
     member: &Member,
     dm_delivery_succeeded: Option<bool>,
-) -> CreateMessage<'a> {
+) -> LogMessage<'a> {
     let mut description = MessageBuilder::new()
         .push("- ")
         .push_bold_line("ユーザー")
@@ -52,5 +52,5 @@ pub(in crate::features::auth) fn create_auth_log_message<'a>(
             Some("ユーザーアイコン".into()),
         );
 
-    create_safe_message().embed(embed)
+    LogMessage::embed(embed)
 }
@@ -0,0 +1,284 @@
+use std::fmt::Display;
+
+use itertools::Itertools;
+use serenity::{
+    all::{MessageBuilder, Timestamp},
+    builder::{CreateComponent, CreateContainerComponent},
+    model::{
+        Color,
+        channel::{GuildChannel, GuildThread, PermissionOverwrite, PermissionOverwriteType},
+        id::GenericChannelId,
+    },
+};
+
+use crate::{
+    app::utils::components::{create_container, create_container_text, create_separator},
+    extensions::MessageBuilderTimestampExt,
+};
+
+pub(in crate::features::channel_logging) enum ChannelLogKind {
+    ChannelCreate,
+    ChannelUpdate,
+    ChannelDelete,
+    ThreadUpdate,
+    ThreadDelete,
+}
+
+impl ChannelLogKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            ChannelLogKind::ChannelCreate => "チャンネル作成ログ",
+            ChannelLogKind::ChannelUpdate => "チャンネル更新ログ",
+            ChannelLogKind::ChannelDelete => "チャンネル削除ログ",
+            ChannelLogKind::ThreadUpdate => "スレッド更新ログ",
+            ChannelLogKind::ThreadDelete => "スレッド削除ログ",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            ChannelLogKind::ChannelCreate => Color::DARK_GREEN,
+            ChannelLogKind::ChannelUpdate | ChannelLogKind::ThreadUpdate => Color::ORANGE,
+            ChannelLogKind::ChannelDelete | ChannelLogKind::ThreadDelete => Color::RED,
+        }
+    }
+}
+
+/**
+変更されたフィールドの変更前と変更後の値
+*/
+pub(in crate::features::channel_logging) struct FieldChange {
+    name: &'static str,
+    before: String,
+    after: String,
+}
+
+impl FieldChange {
+    fn compare<T: PartialEq>(
+        name: &'static str,
+        before: &T,
+        after: &T,
+        format: impl Fn(&T) -> String,
+    ) -> Option<FieldChange> {
+        (before != after).then(|| FieldChange {
+            name,
+            before: format(before),
+            after: format(after),
+        })
+    }
+}
+
+fn format_optional(value: &Option<impl Display>) -> String {
+    value
+        .as_ref()
+        .map(|value| value.to_string())
+        .unwrap_or_else(|| "なし".to_string())
+}
+
+fn format_slowmode(rate_limit_per_user: &Option<impl Display>) -> String {
+    rate_limit_per_user
+        .as_ref()
+        .map(|seconds| format!("{seconds}秒"))
+        .unwrap_or_else(|| "なし".to_string())
+}
+
+fn format_bool(value: &bool) -> String {
+    if *value { "はい" } else { "いいえ" }.to_string()
+}
+
+pub(in crate::features::channel_logging) fn channel_field_changes(
+    old: &GuildChannel,
+    new: &GuildChannel,
+) -> Vec<FieldChange> {
+    [
+        FieldChange::compare("名前", &old.base.name, &new.base.name, |name| name.to_string()),
+        FieldChange::compare("トピック", &old.topic, &new.topic, format_optional),
+        FieldChange::compare(
+            "低速モード",
+            &old.base.rate_limit_per_user,
+            &new.base.rate_limit_per_user,
+            format_slowmode,
+        ),
+        FieldChange::compare("カテゴリ", &old.parent_id, &new.parent_id, format_optional),
+    ]
+    .into_iter()
+    .flatten()
+    .collect_vec()
+}
+
+pub(in crate::features::channel_logging) fn thread_field_changes(
+    old: &GuildThread,
+    new: &GuildThread,
+) -> Vec<FieldChange> {
+    [
+        FieldChange::compare("名前", &old.base.name, &new.base.name, |name| name.to_string()),
+        FieldChange::compare(
+            "アーカイブ",
+            &old.thread_metadata.archived(),
+            &new.thread_metadata.archived(),
+            format_bool,
+        ),
+        FieldChange::compare(
+            "ロック",
+            &old.thread_metadata.locked(),
+            &new.thread_metadata.locked(),
+            format_bool,
+        ),
+        FieldChange::compare("タグ", &old.applied_tags, &new.applied_tags, |tags| {
+            tags.iter().join(", ")
+        }),
+        FieldChange::compare(
+            "低速モード",
+            &old.base.rate_limit_per_user,
+            &new.base.rate_limit_per_user,
+            format_slowmode,
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .collect_vec()
+}
+
+/**
+変更前の状態がキャッシュに無いスレッドの変更を、変更後の状態から推測します。
+
+アーカイブされたスレッドはキャッシュに無いことが多いため、アーカイブされていない状態への変更はアーカイブの解除として扱います。
+*/
+pub(in crate::features::channel_logging) fn uncached_thread_field_changes(new: &GuildThread) -> Vec<FieldChange> {
+    FieldChange::compare("アーカイブ", &true, &new.thread_metadata.archived(), format_bool)
+        .into_iter()
+        .collect_vec()
+}
+
+fn format_overwrite_target(kind: &PermissionOverwriteType) -> String {
+    match kind {
+        PermissionOverwriteType::Member(id) => format!("<@{id}>"),
+        PermissionOverwriteType::Role(id) => format!("<@&{id}>"),
+        _ => "<不明な対象>".to_string(),
+    }
+}
+
+/**
+権限の上書きの変更を対象ごとに整形します。
+*/
+pub(in crate::features::channel_logging) fn format_permission_overwrite_changes(
+    old: &[PermissionOverwrite],
+    new: &[PermissionOverwrite],
+) -> Vec<String> {
+    let find = |overwrites: &[PermissionOverwrite], kind: &PermissionOverwriteType| {
+        overwrites.iter().find(|overwrite| overwrite.kind == *kind).cloned()
+    };
+
+    let mut kinds: Vec<PermissionOverwriteType> = Vec::new();
+    for overwrite in old.iter().chain(new) {
+        if !kinds.contains(&overwrite.kind) {
+            kinds.push(overwrite.kind);
+        }
+    }
+
+    kinds
+        .into_iter()
+        .filter_map(|kind| {
+            let before = find(old, &kind);
+            let after = find(new, &kind);
+            let target = format_overwrite_target(&kind);
+
+            match (before, after) {
+                (None, Some(_)) => Some(format!("- {target}: 追加")),
+                (Some(_), None) => Some(format!("- {target}: 削除")),
+                (Some(before), Some(after)) if before.allow != after.allow || before.deny != after.deny => {
+                    let mut parts = Vec::new();
+                    let added_allow = after.allow - before.allow;
+                    let removed_allow = before.allow - after.allow;
+                    let added_deny = after.deny - before.deny;
+                    let removed_deny = before.deny - after.deny;
+
+                    if !added_allow.is_empty() {
+                        parts.push(format!("許可 +{}", added_allow.get_permission_names().join(", ")));
+                    }
+                    if !removed_allow.is_empty() {
+                        parts.push(format!("許可 -{}", removed_allow.get_permission_names().join(", ")));
+                    }
+                    if !added_deny.is_empty() {
+                        parts.push(format!("拒否 +{}", added_deny.get_permission_names().join(", ")));
+                    }
+                    if !removed_deny.is_empty() {
+                        parts.push(format!("拒否 -{}", removed_deny.get_permission_names().join(", ")));
+                    }
+
+                    Some(format!("- {target}: {}", parts.join(" / ")))
+                }
+                _ => None,
+            }
+        })
+        .collect_vec()
+}
+
+pub(in crate::features::channel_logging) fn build_channel_basic_info(
+    channel_id: GenericChannelId,
+    name: Option<&str>,
+    parent_id: Option<GenericChannelId>,
+) -> String {
+    let mut builder = MessageBuilder::new()
+        .push_line("### **基本情報**")
+        .push_bold_safe("チャンネル: ")
+        .push(format!("<#{channel_id}> ").as_str())
+        .push_mono_line_safe(&*channel_id.to_string());
+
+    if let Some(name) = name {
+        builder = builder.push_bold_safe("名前: ").push_line_safe(name);
+    }
+
+    if let Some(parent_id) = parent_id {
+        builder = builder
+            .push_bold_safe("親チャンネル: ")
+            .push(format!("<#{parent_id}> ").as_str())
+            .push_mono_line_safe(&*parent_id.to_string());
+    }
+
+    builder
+        .push_bold_safe("作成日時: ")
+        .push_short_date_medium_timestamp_line(channel_id.created_at())
+        .push_bold_safe("記録日時: ")
+        .push_short_date_medium_timestamp(Timestamp::now())
+        .build()
+}
+
+pub(in crate::features::channel_logging) fn build_channel_log_container<'a>(
+    log_kind: &ChannelLogKind,
+    basic_info: String,
+    field_changes: &[FieldChange],
+    permission_changes: &[String],
+) -> CreateComponent<'a> {
+    let mut components: Vec<CreateContainerComponent<'a>> = vec![
+        create_container_text(format!("### **{}**", log_kind.title())),
+        create_separator(false),
+        create_container_text(basic_info),
+    ];
+
+    if !field_changes.is_empty() {
+        let diff = field_changes
+            .iter()
+            .map(|change| format!("{}:\n- {}\n+ {}", change.name, change.before, change.after))
+            .join("\n");
+
+        components.extend([
+            create_separator(false),
+            create_container_text(
+                MessageBuilder::new()
+                    .push_line("### **変更内容**")
+                    .push_codeblock_safe(diff.as_str(), Some("diff"))
+                    .build(),
+            ),
+        ]);
+    }
+
+    if !permission_changes.is_empty() {
+        components.extend([
+            create_separator(false),
+            create_container_text(format!("### **権限の変更**\n{}", permission_changes.join("\n"))),
+        ]);
+    }
+
+    create_container(components, Some(log_kind.color()), false)
+}
@@ -0,0 +1,159 @@
+use anyhow::Context as _;
+use serenity::{
+    all::prelude::Context,
+    builder::CreateComponent,
+    model::{
+        channel::{GuildChannel, GuildThread, PartialGuildThread},
+        event::FullEvent,
+        id::GuildId,
+    },
+};
+use valine_bot_macros::event_handler;
+
+use crate::{
+    app::{AppError, BotDataExt},
+    features::channel_logging::component_builder::{
+        ChannelLogKind, build_channel_basic_info, build_channel_log_container, channel_field_changes,
+        format_permission_overwrite_changes, thread_field_changes, uncached_thread_field_changes,
+    },
+    utils::{create_components_v2_message, send_message},
+};
+
+async fn send_channel_log(ctx: &Context, guild_id: GuildId, component: CreateComponent<'_>) -> Result<(), AppError> {
+    let config = ctx.app_config().await;
+    let Some(channel_logging) = &config.channel_logging else {
+        return Ok(());
+    };
+
+    send_message(
+        ctx,
+        &channel_logging.channel_id_for(guild_id),
+        create_components_v2_message(vec![component]),
+    )
+    .await
+    .context("Failed to send channel log")?;
+
+    Ok(())
+}
+
+fn build_guild_channel_basic_info(channel: &GuildChannel) -> String {
+    build_channel_basic_info(
+        channel.id.widen(),
+        Some(&channel.base.name),
+        channel.parent_id.map(|id| id.widen()),
+    )
+}
+
+async fn handle_channel_create(ctx: &Context, channel: &GuildChannel) -> Result<(), AppError> {
+    let log_kind = ChannelLogKind::ChannelCreate;
+    send_channel_log(
+        ctx,
+        channel.base.guild_id,
+        build_channel_log_container(&log_kind, build_guild_channel_basic_info(channel), &[], &[]),
+    )
+    .await
+}
+
+async fn handle_channel_update(ctx: &Context, old: &Option<GuildChannel>, new: &GuildChannel) -> Result<(), AppError> {
+    // 変更前の状態がキャッシュに無い場合は差分が分からないため記録しない
+    let Some(old) = old else {
+        return Ok(());
+    };
+
+    let field_changes = channel_field_changes(old, new);
+    let permission_changes =
+        format_permission_overwrite_changes(&old.permission_overwrites, &new.permission_overwrites);
+    if field_changes.is_empty() && permission_changes.is_empty() {
+        return Ok(());
+    }
+
+    let log_kind = ChannelLogKind::ChannelUpdate;
+    send_channel_log(
+        ctx,
+        new.base.guild_id,
+        build_channel_log_container(
+            &log_kind,
+            build_guild_channel_basic_info(new),
+            &field_changes,
+            &permission_changes,
+        ),
+    )
+    .await
+}
+
+async fn handle_channel_delete(ctx: &Context, channel: &GuildChannel) -> Result<(), AppError> {
+    let log_kind = ChannelLogKind::ChannelDelete;
+    send_channel_log(
+        ctx,
+        channel.base.guild_id,
+        build_channel_log_container(&log_kind, build_guild_channel_basic_info(channel), &[], &[]),
+    )
+    .await
+}
+
+fn build_thread_basic_info(thread: &GuildThread) -> String {
+    let mut basic_info = build_channel_basic_info(
+        thread.id.widen(),
+        Some(&thread.base.name),
+        Some(thread.parent_id.widen()),
+    );
+    basic_info.push_str(&format!("\n**作成者:** <@{0}> `{0}`", thread.owner_id));
+    basic_info
+}
+
+async fn handle_thread_update(ctx: &Context, old: &Option<GuildThread>, new: &GuildThread) -> Result<(), AppError> {
+    // 変更前の状態がキャッシュに無い場合は、アーカイブの解除のみ記録する
+    let field_changes = match old {
+        Some(old) => thread_field_changes(old, new),
+        None => uncached_thread_field_changes(new),
+    };
+    if field_changes.is_empty() {
+        return Ok(());
+    }
+
+    let log_kind = ChannelLogKind::ThreadUpdate;
+    send_channel_log(
+        ctx,
+        new.base.guild_id,
+        build_channel_log_container(&log_kind, build_thread_basic_info(new), &field_changes, &[]),
+    )
+    .await
+}
+
+async fn handle_thread_delete(
+    ctx: &Context,
+    thread: &PartialGuildThread,
+    full_thread_data: &Option<GuildThread>,
+) -> Result<(), AppError> {
+    // キャッシュに無いスレッドは名前や作成者が分からないため ID のみを記録する
+    let basic_info = match full_thread_data {
+        Some(full_thread) => build_thread_basic_info(full_thread),
+        None => build_channel_basic_info(thread.id.widen(), None, Some(thread.parent_id.widen())),
+    };
+
+    let log_kind = ChannelLogKind::ThreadDelete;
+    send_channel_log(
+        ctx,
+        thread.guild_id,
+        build_channel_log_container(&log_kind, basic_info, &[], &[]),
+    )
+    .await
+}
+
+#[event_handler]
+pub async fn handle_channel_logging_event(ctx: &Context, event: &FullEvent) -> Result<(), AppError> {
+    match event {
+        FullEvent::ChannelCreate { channel, .. } => handle_channel_create(ctx, channel).await?,
+        FullEvent::ChannelUpdate { old, new, .. } => handle_channel_update(ctx, old, new).await?,
+        FullEvent::ChannelDelete { channel, .. } => handle_channel_delete(ctx, channel).await?,
+        FullEvent::ThreadUpdate { old, new, .. } => handle_thread_update(ctx, old, new).await?,
+        FullEvent::ThreadDelete {
+            thread,
+            full_thread_data,
+            ..
+        } => handle_thread_delete(ctx, thread, full_thread_data).await?,
+        _ => {}
+    }
+
+    Ok(())
+}
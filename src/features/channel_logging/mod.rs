@@ -0,0 +1,4 @@
+mod component_builder;
+mod handler;
+
+pub use handler::handle_channel_logging_event;
@@ -17,8 +17,11 @@ use tracing::error;
 use valine_bot_macros::event_handler;
 
 use crate::{
-    app::{AppError, BotDataExt, BotError},
-    utils::{create_message, create_safe_message, send_message},
+    app::{
+        AppError, BotDataExt, BotError,
+        utils::log_message::{LogMessage, send_log_message},
+    },
+    utils::create_message,
 };
 
 struct MessageFingerprint {
@@ -203,13 +206,9 @@ pub async fn handle_honeypot_event(ctx: &Context, event: &FullEvent) -> Result<(
                 Some("ユーザーアイコン".into()),
             );
 
-        send_message(
-            ctx,
-            &config.honeypot.log_channel_id,
-            create_safe_message().add_embed(embed),
-        )
-        .await
-        .context("Failed to send honeypot log")?;
+        send_log_message(ctx, config.honeypot.log_destination(), LogMessage::embed(embed))
+            .await
+            .context("Failed to send honeypot log")?;
     }
 
     Ok(())
@@ -0,0 +1,159 @@
+use poise::say_reply;
+use serenity::{
+    all::MessageBuilder,
+    model::id::{GenericChannelId, GuildId},
+};
+
+use crate::{
+    app::{AppContext, AppError, BotDataExt},
+    features::message_cache::controller::{BackfillExt, BackfillTarget},
+    utils::format_duration,
+};
+
+/// 表示する取得中のチャンネルの最大数
+const MAX_LISTED_IN_FLIGHT_CHANNELS: usize = 10;
+
+/// 過去メッセージの取得を管理
+#[poise::command(
+    slash_command,
+    ephemeral,
+    owners_only,
+    dm_only,
+    subcommands(
+        "backfill_status",
+        "backfill_pause",
+        "backfill_resume",
+        "backfill_rerun",
+        "backfill_concurrency"
+    ),
+    subcommand_required
+)]
+pub async fn backfill(_ctx: AppContext<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// 過去メッセージの取得状況を表示
+#[poise::command(slash_command, ephemeral, owners_only, dm_only, rename = "status")]
+pub async fn backfill_status(ctx: AppContext<'_>) -> Result<(), AppError> {
+    let status = ctx.backfill()?.status();
+
+    let state = match (status.elapsed, status.finished, status.paused) {
+        (None, _, _) => "未開始",
+        (_, true, _) => "完了",
+        (_, false, true) => "一時停止中",
+        (_, false, false) => "取得中",
+    };
+
+    let mut builder = MessageBuilder::new()
+        .push_bold("状態: ")
+        .push_line(state)
+        .push_bold("同時取得数: ")
+        .push_line(
+            format!(
+                "{} (設定値 {}、レート制限による減速段階 {})",
+                status.effective_concurrent_channels, status.concurrent_channels, status.throttle_level
+            )
+            .as_str(),
+        )
+        .push_bold("チャンネル: ")
+        .push_line(
+            format!(
+                "完了 {} / 取得中 {} / 待機中 {}",
+                status.done,
+                status.in_flight.len(),
+                status.pending
+            )
+            .as_str(),
+        )
+        .push_bold("取得したメッセージ: ")
+        .push_line(format!("{}件", status.cached_messages).as_str());
+
+    if let Some(elapsed) = status.elapsed {
+        builder = builder
+            .push_bold("経過時間: ")
+            .push_line(format_duration(elapsed, 2).as_str());
+    }
+    if let Some(eta) = status.eta() {
+        builder = builder
+            .push_bold("残り時間 (推定): ")
+            .push_line(format_duration(eta, 2).as_str());
+    }
+
+    if !status.in_flight.is_empty() {
+        builder = builder.push_bold_line("取得中のチャンネル:");
+        for channel_id in status.in_flight.iter().take(MAX_LISTED_IN_FLIGHT_CHANNELS) {
+            builder = builder.push_line(format!("- <#{channel_id}> `{channel_id}`").as_str());
+        }
+        if status.in_flight.len() > MAX_LISTED_IN_FLIGHT_CHANNELS {
+            builder = builder
+                .push_line(format!("- ... 他{}件", status.in_flight.len() - MAX_LISTED_IN_FLIGHT_CHANNELS).as_str());
+        }
+    }
+
+    if status.error_count > 0 {
+        builder = builder.push_bold_line(format!("エラー ({}件、直近のもの):", status.error_count).as_str());
+        for (channel_id, error) in &status.recent_errors {
+            builder = builder
+                .push(format!("- <#{channel_id}> `{channel_id}`: ").as_str())
+                .push_line_safe(error.as_str());
+        }
+    }
+
+    say_reply(ctx, builder.build()).await?;
+    Ok(())
+}
+
+/// 過去メッセージの取得を一時停止
+#[poise::command(slash_command, ephemeral, owners_only, dm_only, rename = "pause")]
+pub async fn backfill_pause(ctx: AppContext<'_>) -> Result<(), AppError> {
+    ctx.backfill()?.set_paused(true);
+    say_reply(ctx, "過去メッセージの取得を一時停止しました。").await?;
+    Ok(())
+}
+
+/// 過去メッセージの取得を再開
+#[poise::command(slash_command, ephemeral, owners_only, dm_only, rename = "resume")]
+pub async fn backfill_resume(ctx: AppContext<'_>) -> Result<(), AppError> {
+    ctx.backfill()?.set_paused(false);
+    say_reply(ctx, "過去メッセージの取得を再開しました。").await?;
+    Ok(())
+}
+
+/// チャンネルまたはギルドの過去メッセージを取得済みのものも含めて再取得
+#[poise::command(slash_command, ephemeral, owners_only, dm_only, rename = "rerun")]
+pub async fn backfill_rerun(
+    ctx: AppContext<'_>,
+    #[description = "再取得するチャンネル・スレッドのID"] channel_id: Option<GenericChannelId>,
+    #[description = "再取得するギルドのID"] guild_id: Option<GuildId>,
+) -> Result<(), AppError> {
+    let target = match (channel_id, guild_id) {
+        (Some(id), None) => BackfillTarget::Channel(id),
+        (None, Some(id)) => BackfillTarget::Guild(id),
+        _ => {
+            say_reply(ctx, "チャンネルIDとギルドIDのどちらか一方を指定してください。").await?;
+            return Ok(());
+        }
+    };
+
+    ctx.backfill()?.request(target);
+    say_reply(ctx, "再取得を予約しました。実行中の取得が完了した後に開始されます。").await?;
+    Ok(())
+}
+
+/// 同時に過去メッセージを取得するチャンネル数を変更
+#[poise::command(slash_command, ephemeral, owners_only, dm_only, rename = "concurrency")]
+pub async fn backfill_concurrency(
+    ctx: AppContext<'_>,
+    #[description = "同時に取得するチャンネル数"]
+    #[min = 1]
+    #[max = 255]
+    concurrent_channels: u8,
+) -> Result<(), AppError> {
+    ctx.backfill()?.set_concurrent_channels(concurrent_channels.into());
+    say_reply(
+        ctx,
+        format!("同時に取得するチャンネル数を {concurrent_channels} に変更しました。"),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::DashSet;
+use serenity::model::id::{GenericChannelId, GuildId, MessageId};
+use tokio::{
+    sync::{Semaphore, SemaphorePermit, mpsc, watch},
+    time::{sleep, sleep_until},
+};
+use tracing::{info, warn};
+
+use crate::{
+    app::{BotDataExt, BotError},
+    features::message_cache::throttle::AdaptiveThrottle,
+};
+
+/// 同時に取得するチャンネル数の上限 (実際の同時実行数は `BackfillController` で制御する)
+pub(in crate::features::message_cache) const MAX_CONCURRENT_CHANNELS: usize = u8::MAX as usize;
+
+/// 表示のために保持するエラーの最大数
+const MAX_RECENT_ERRORS: usize = 10;
+
+/**
+再取得の対象
+*/
+#[derive(Debug, Clone, Copy)]
+pub enum BackfillTarget {
+    Channel(GenericChannelId),
+    Guild(GuildId),
+    /// 再接続までの間に送信されたメッセージ
+    Gaps,
+}
+
+#[derive(Default)]
+struct BackfillProgress {
+    pending: usize,
+    in_flight: HashSet<GenericChannelId>,
+    done: usize,
+    cached_messages: usize,
+    started_at: Option<Instant>,
+    finished_at: Option<Instant>,
+    recent_errors: VecDeque<(GenericChannelId, String)>,
+    error_count: usize,
+}
+
+/**
+過去メッセージの取得状況
+*/
+pub struct BackfillStatus {
+    pub paused: bool,
+    pub concurrent_channels: usize,
+    pub effective_concurrent_channels: usize,
+    pub throttle_level: u32,
+    pub pending: usize,
+    pub in_flight: Vec<GenericChannelId>,
+    pub done: usize,
+    pub cached_messages: usize,
+    pub elapsed: Option<Duration>,
+    pub finished: bool,
+    pub recent_errors: Vec<(GenericChannelId, String)>,
+    pub error_count: usize,
+}
+
+impl BackfillStatus {
+    /**
+    完了したチャンネルの平均所要時間から、残りのチャンネルの完了までの時間を推定します。
+    */
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.pending + self.in_flight.len();
+        if self.finished || self.done == 0 || remaining == 0 {
+            return None;
+        }
+
+        Some(self.elapsed?.mul_f64(remaining as f64 / self.done as f64))
+    }
+}
+
+/**
+チャンネルの取得中に保持する許可
+
+同時実行数が減らされている場合は、返却せずに破棄します。
+*/
+pub struct BackfillPermit<'a> {
+    controller: &'a BackfillController,
+    permit: Option<SemaphorePermit<'a>>,
+}
+
+impl Drop for BackfillPermit<'_> {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+
+        let debt = &self.controller.permit_debt;
+        if debt
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |debt| debt.checked_sub(1))
+            .is_ok()
+        {
+            permit.forget();
+        }
+    }
+}
+
+/**
+過去メッセージの取得の進捗を記録し、一時停止・再開・同時実行数の変更・再取得の要求を受け付けます。
+*/
+pub struct BackfillController {
+    paused: watch::Sender<bool>,
+    semaphore: Semaphore,
+    /// 設定された同時実行数 (レート制限による調整前)
+    concurrent_channels: AtomicUsize,
+    /// 発行済みの許可の数 (レート制限による調整後の同時実行数)
+    permits: Mutex<usize>,
+    /// 同時実行数を減らした際に、使用中のため破棄できなかった許可の数
+    permit_debt: AtomicUsize,
+    throttle: AdaptiveThrottle,
+    progress: Mutex<BackfillProgress>,
+    /// 過去メッセージを取得したチャンネル (キャッシュ上でアーカイブ済みスレッドを辿れないため記録する)
+    cached_channel_ids: DashSet<GenericChannelId>,
+    /// 再接続時点でキャッシュ上の最新のメッセージ (切断中のメッセージの取得を始める位置)
+    gap_starts: Mutex<HashMap<GenericChannelId, MessageId>>,
+    requests: mpsc::UnboundedSender<BackfillTarget>,
+    request_receiver: Mutex<Option<mpsc::UnboundedReceiver<BackfillTarget>>>,
+}
+
+impl BackfillController {
+    pub fn new() -> Self {
+        let (requests, request_receiver) = mpsc::unbounded_channel();
+
+        Self {
+            paused: watch::Sender::new(false),
+            semaphore: Semaphore::new(0),
+            concurrent_channels: AtomicUsize::new(0),
+            permits: Mutex::new(0),
+            permit_debt: AtomicUsize::new(0),
+            throttle: AdaptiveThrottle::new(),
+            progress: Mutex::new(BackfillProgress::default()),
+            cached_channel_ids: DashSet::new(),
+            gap_starts: Mutex::new(HashMap::new()),
+            requests,
+            request_receiver: Mutex::new(Some(request_receiver)),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.send_replace(paused);
+    }
+
+    /**
+    一時停止されている場合は再開されるまで、レート制限を受けている場合は解除されるまで待機します。
+    */
+    pub async fn wait_until_ready(&self) {
+        if self.is_paused() {
+            let mut paused = self.paused.subscribe();
+            let _ = paused.wait_for(|paused| !paused).await;
+        }
+
+        if self.throttle.recover() {
+            info!("Backfill throttle decreased to level {}", self.throttle.level());
+            self.apply_concurrency();
+        }
+
+        if let Some(backoff_until) = self.throttle.backoff_until() {
+            sleep_until(backoff_until.into()).await;
+        }
+    }
+
+    /**
+    リクエストの時間枠ごとの待機を、レート制限の状況に応じて広げて行います。
+    */
+    pub async fn wait_request_window(&self, request_window: Duration) {
+        sleep(self.throttle.scale_delay(request_window)).await;
+    }
+
+    /**
+    レート制限の通知を受けて取得ペースを落とします。
+    */
+    pub fn on_ratelimit(&self, timeout: Duration) {
+        if self.throttle.on_ratelimit(timeout) {
+            warn!("Backfill throttle increased to level {}", self.throttle.level());
+            self.apply_concurrency();
+        }
+    }
+
+    pub fn throttle_level(&self) -> u32 {
+        self.throttle.level()
+    }
+
+    pub fn effective_concurrent_channels(&self) -> usize {
+        *self.permits.lock().unwrap()
+    }
+
+    pub fn concurrent_channels(&self) -> usize {
+        self.concurrent_channels.load(Ordering::Relaxed)
+    }
+
+    /**
+    同時に取得するチャンネル数が未設定の場合のみ設定します。
+    */
+    pub fn init_concurrent_channels(&self, concurrent_channels: usize) {
+        if self.concurrent_channels() == 0 {
+            self.set_concurrent_channels(concurrent_channels);
+        }
+    }
+
+    /**
+    同時に取得するチャンネル数を変更します。実行中のチャンネルは完了するまで続行されます。
+    */
+    pub fn set_concurrent_channels(&self, concurrent_channels: usize) {
+        self.concurrent_channels
+            .store(concurrent_channels.max(1), Ordering::Relaxed);
+        self.apply_concurrency();
+    }
+
+    /**
+    設定された同時実行数とレート制限の状況から、発行する許可の数を調整します。
+    */
+    fn apply_concurrency(&self) {
+        let mut permits = self.permits.lock().unwrap();
+        let target = self.throttle.scale_concurrency(self.concurrent_channels());
+
+        if target > *permits {
+            // 破棄待ちの許可がある場合は、それを取り消して補う
+            let increase = target - *permits;
+            let debt = self
+                .permit_debt
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |debt| {
+                    Some(debt.saturating_sub(increase))
+                })
+                .unwrap_or_default();
+            self.semaphore.add_permits(increase - debt.min(increase));
+        } else if target < *permits {
+            let excess = *permits - target;
+            let forgotten = self.semaphore.forget_permits(excess);
+            // 使用中の許可は返却時に破棄する
+            self.permit_debt.fetch_add(excess - forgotten, Ordering::Relaxed);
+        }
+
+        *permits = target;
+    }
+
+    /**
+    チャンネルの取得を開始する許可を得るまで待機します。
+    */
+    pub async fn acquire(&self) -> BackfillPermit<'_> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("backfill semaphore is never closed");
+
+        BackfillPermit {
+            controller: self,
+            permit: Some(permit),
+        }
+    }
+
+    /**
+    再接続時点の各チャンネルの最新のメッセージを記録します。
+
+    取得の前に再び再接続した場合に切断中のメッセージを取りこぼさないよう、より古い位置を残します。
+    */
+    pub fn record_gap_starts(&self, gap_starts: HashMap<GenericChannelId, MessageId>) {
+        let mut recorded = self.gap_starts.lock().unwrap();
+        for (channel_id, message_id) in gap_starts {
+            recorded
+                .entry(channel_id)
+                .and_modify(|recorded_id| *recorded_id = (*recorded_id).min(message_id))
+                .or_insert(message_id);
+        }
+    }
+
+    /**
+    記録された再接続時点の最新のメッセージを取り出します。
+    */
+    pub fn take_gap_starts(&self) -> HashMap<GenericChannelId, MessageId> {
+        std::mem::take(&mut *self.gap_starts.lock().unwrap())
+    }
+
+    pub fn request(&self, target: BackfillTarget) {
+        let _ = self.requests.send(target);
+    }
+
+    /**
+    再取得の要求を受け取るレシーバーを取り出します。最初の 1 回のみ `Some` を返します。
+    */
+    pub fn take_request_receiver(&self) -> Option<mpsc::UnboundedReceiver<BackfillTarget>> {
+        self.request_receiver.lock().unwrap().take()
+    }
+
+    /**
+    進捗をリセットして取得を開始します。
+    */
+    pub fn start(&self) {
+        *self.progress.lock().unwrap() = BackfillProgress {
+            started_at: Some(Instant::now()),
+            ..Default::default()
+        };
+    }
+
+    pub fn add_pending(&self, channel_count: usize) {
+        self.progress.lock().unwrap().pending += channel_count;
+    }
+
+    pub fn cached_channel_ids(&self) -> Vec<GenericChannelId> {
+        self.cached_channel_ids.iter().map(|id| *id).collect()
+    }
+
+    pub fn begin_channel(&self, channel_id: GenericChannelId) {
+        self.cached_channel_ids.insert(channel_id);
+        let mut progress = self.progress.lock().unwrap();
+        progress.pending = progress.pending.saturating_sub(1);
+        progress.in_flight.insert(channel_id);
+    }
+
+    pub fn add_cached_messages(&self, count: usize) {
+        self.progress.lock().unwrap().cached_messages += count;
+    }
+
+    pub fn finish_channel(&self, channel_id: GenericChannelId, error: Option<String>) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.in_flight.remove(&channel_id);
+        progress.done += 1;
+
+        if let Some(error) = error {
+            progress.error_count += 1;
+            progress.recent_errors.push_back((channel_id, error));
+            if progress.recent_errors.len() > MAX_RECENT_ERRORS {
+                progress.recent_errors.pop_front();
+            }
+        }
+    }
+
+    /**
+    取得対象に含まれなかったチャンネルを待機中から除外します。
+    */
+    pub fn skip_channel(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.pending = progress.pending.saturating_sub(1);
+    }
+
+    pub fn finish(&self) {
+        self.progress.lock().unwrap().finished_at = Some(Instant::now());
+    }
+
+    pub fn status(&self) -> BackfillStatus {
+        let progress = self.progress.lock().unwrap();
+
+        BackfillStatus {
+            paused: self.is_paused(),
+            concurrent_channels: self.concurrent_channels(),
+            effective_concurrent_channels: self.effective_concurrent_channels(),
+            throttle_level: self.throttle_level(),
+            pending: progress.pending,
+            in_flight: progress.in_flight.iter().copied().collect(),
+            done: progress.done,
+            cached_messages: progress.cached_messages,
+            elapsed: progress
+                .started_at
+                .map(|started_at| progress.finished_at.unwrap_or_else(Instant::now) - started_at),
+            finished: progress.finished_at.is_some(),
+            recent_errors: progress.recent_errors.iter().cloned().collect(),
+            error_count: progress.error_count,
+        }
+    }
+}
+
+/**
+`BotData` に登録された `BackfillController` を取得します。
+*/
+pub(in crate::features::message_cache) trait BackfillExt {
+    fn backfill(&self) -> Result<Arc<BackfillController>, BotError>;
+}
+
+impl<T: BotDataExt> BackfillExt for T {
+    fn backfill(&self) -> Result<Arc<BackfillController>, BotError> {
+        self.extension()
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::{DateTime, Duration, Utc};
+use itertools::Itertools;
+use serenity::{
+    all::Context,
+    json::json,
+    model::{
+        event::MessageDeleteBulkEvent,
+        id::{GenericChannelId, MessageId},
+    },
+};
+use tracing::error;
+
+/**
+キャッシュ上のチャンネル (ギルドのチャンネル・アクティブなスレッドと、過去メッセージを取得したチャンネル) を列挙します。
+*/
+fn cached_channel_ids(ctx: &Context, backfilled_channel_ids: &[GenericChannelId]) -> Vec<GenericChannelId> {
+    ctx.cache
+        .guilds()
+        .into_iter()
+        .filter_map(|guild_id| guild_id.to_guild_cached(&ctx.cache).map(|guild| guild.clone()))
+        .flat_map(|guild| {
+            guild
+                .channels
+                .iter()
+                .map(|channel| channel.id.widen())
+                .chain(guild.threads.iter().map(|thread| thread.id.widen()))
+                .collect_vec()
+        })
+        .chain(backfilled_channel_ids.iter().copied())
+        .unique()
+        .collect()
+}
+
+/**
+指定した日時より前に作成されたメッセージのIDを返します。作成日時はIDから求めます。
+*/
+fn expired_message_ids(message_ids: impl IntoIterator<Item = MessageId>, cutoff: DateTime<Utc>) -> Vec<MessageId> {
+    let cutoff = cutoff.timestamp();
+    message_ids
+        .into_iter()
+        .filter(|id| id.created_at().unix_timestamp() < cutoff)
+        .collect()
+}
+
+/**
+保持期間を過ぎたメッセージをキャッシュから削除し、削除した件数を返します。
+
+Serenity のキャッシュには個別に削除する API が無いため、一括削除イベントをキャッシュに適用して削除します。
+*/
+pub(in crate::features::message_cache) fn evict_expired_messages(
+    ctx: &Context,
+    backfilled_channel_ids: &[GenericChannelId],
+    max_age: Duration,
+) -> usize {
+    let cutoff = Utc::now() - max_age;
+    let mut evicted_count = 0;
+
+    for channel_id in cached_channel_ids(ctx, backfilled_channel_ids) {
+        let Some(messages) = ctx.cache.channel_messages(channel_id) else {
+            continue;
+        };
+        let expired_ids = expired_message_ids(messages.iter().map(|message| message.id), cutoff);
+        drop(messages);
+
+        if expired_ids.is_empty() {
+            continue;
+        }
+
+        let event = serenity::json::from_value::<MessageDeleteBulkEvent>(json!({
+            "channel_id": channel_id,
+            "ids": expired_ids,
+        }));
+        match event {
+            Ok(mut event) => {
+                ctx.cache.update(&mut event);
+                evicted_count += expired_ids.len();
+            }
+            Err(error) => error!("Failed to build cache eviction event for channel {channel_id}: {error}"),
+        }
+    }
+
+    evicted_count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Discord のエポック (2015-01-01T00:00:00Z) のミリ秒
+    const DISCORD_EPOCH_MILLIS: i64 = 1_420_070_400_000;
+
+    fn message_id_at(timestamp: DateTime<Utc>) -> MessageId {
+        MessageId::new(((timestamp.timestamp_millis() - DISCORD_EPOCH_MILLIS) as u64) << 22)
+    }
+
+    #[test]
+    fn expired_message_ids_keeps_messages_within_max_age() {
+        let now = Utc::now();
+        let old = message_id_at(now - Duration::days(31));
+        let older = message_id_at(now - Duration::days(400));
+        let recent = message_id_at(now - Duration::days(29));
+        let latest = message_id_at(now);
+
+        let expired = expired_message_ids([old, recent, older, latest], now - Duration::days(30));
+        assert_eq!(expired, [old, older]);
+    }
+
+    #[test]
+    fn expired_message_ids_is_empty_without_old_messages() {
+        let now = Utc::now();
+        let ids = [message_id_at(now - Duration::hours(1)), message_id_at(now)];
+        assert!(expired_message_ids(ids, now - Duration::days(30)).is_empty());
+    }
+}
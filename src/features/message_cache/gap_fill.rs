@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use futures::{StreamExt, stream};
+use itertools::Itertools;
+use serenity::{
+    all::Context,
+    builder::GetMessages,
+    model::id::{GenericChannelId, GuildId, MessageId},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    app::{AppError, BotDataExt},
+    features::message_cache::{
+        controller::{BackfillController, MAX_CONCURRENT_CHANNELS},
+        progress_store::BackfillProgressStore,
+    },
+    utils::channel_ancestor_ids,
+};
+
+/// 1 回のリクエストで取得するメッセージの最大数
+const MESSAGES_PER_REQUEST: u8 = 100;
+
+/**
+取得対象のギルドのチャンネルについて、キャッシュ上で最新のメッセージを返します。
+
+再接続の直後に呼び出し、切断中のメッセージを取得する起点として記録します。
+再接続後に受信したメッセージがキャッシュに載ると、起点が切断中の期間より後になってしまうためです。
+*/
+pub(in crate::features::message_cache) fn newest_cached_message_ids(
+    ctx: &Context,
+    target_guild_ids: &[GuildId],
+) -> HashMap<GenericChannelId, MessageId> {
+    target_guild_ids
+        .iter()
+        .filter_map(|guild_id| guild_id.to_guild_cached(&ctx.cache).map(|guild| guild.clone()))
+        .flat_map(|guild| {
+            guild
+                .channels
+                .iter()
+                .map(|channel| channel.id.widen())
+                .chain(guild.threads.iter().map(|thread| thread.id.widen()))
+                .collect_vec()
+        })
+        .filter_map(|channel_id| {
+            let messages = ctx.cache.channel_messages(channel_id)?;
+            let newest_message_id = messages.iter().map(|message| message.id).max()?;
+            Some((channel_id, newest_message_id))
+        })
+        .collect()
+}
+
+/**
+チャンネルの取得を始めるメッセージを返します。再接続時点の記録が無い場合は取得済みの記録を使用します。
+*/
+async fn gap_start(
+    ctx: &Context,
+    gap_starts: &HashMap<GenericChannelId, MessageId>,
+    channel_id: GenericChannelId,
+) -> Option<MessageId> {
+    if let Some(message_id) = gap_starts.get(&channel_id) {
+        return Some(*message_id);
+    }
+
+    match BackfillProgressStore::high_water_mark(&ctx.database(), channel_id).await {
+        Ok(high_water_mark) => high_water_mark,
+        Err(error) => {
+            error!("Failed to get backfill progress for channel {channel_id}: {error:#}");
+            None
+        }
+    }
+}
+
+/**
+指定したメッセージより新しいメッセージを全て取得してキャッシュに載せ、取得した件数を返します。
+*/
+async fn fill_channel_gap(
+    ctx: &Context,
+    controller: &BackfillController,
+    channel_id: GenericChannelId,
+    mut after: MessageId,
+) -> Result<usize, AppError> {
+    let config = ctx.app_config().await;
+    let requests_per_window: usize = config.message_cache.requests_per_window.max(1).into();
+    let mut fetched_count = 0;
+    let mut request_count = 0;
+
+    loop {
+        controller.wait_until_ready().await;
+
+        // Context を渡すと Serenity 標準キャッシュに取得結果が載るようになる
+        let messages = channel_id
+            .messages(ctx, GetMessages::new().after(after).limit(MESSAGES_PER_REQUEST))
+            .await?;
+
+        fetched_count += messages.len();
+        controller.add_cached_messages(messages.len());
+        if let Some(newest_message_id) = messages.iter().map(|message| message.id).max() {
+            after = newest_message_id;
+        }
+
+        if messages.len() < MESSAGES_PER_REQUEST.into() {
+            break;
+        }
+
+        request_count += 1;
+        if request_count % requests_per_window == 0 {
+            controller
+                .wait_request_window(config.message_cache.request_window)
+                .await;
+        }
+    }
+
+    if fetched_count > 0 {
+        BackfillProgressStore::update_high_water_mark(&ctx.database(), channel_id, after).await?;
+    }
+
+    Ok(fetched_count)
+}
+
+/**
+取得対象のギルドのアクティブなチャンネルのうち、ゲートウェイのキャッシュ上の最新のメッセージが
+取得済みのメッセージより新しいものを、取得を始めるメッセージと共に列挙します。
+
+アーカイブされたスレッドには切断中もメッセージが送信されないため、対象としません。
+*/
+async fn gap_targets(
+    ctx: &Context,
+    gap_starts: &HashMap<GenericChannelId, MessageId>,
+) -> Vec<(GenericChannelId, MessageId)> {
+    let config = ctx.app_config().await;
+    let cache_config = &config.message_cache;
+
+    let mut candidates = Vec::new();
+    for guild_id in &cache_config.target_guild_ids {
+        let Some(guild) = guild_id.to_guild_cached(&ctx.cache) else {
+            continue;
+        };
+
+        let channels = guild
+            .channels
+            .iter()
+            .filter(|channel| channel.is_text_based())
+            .map(|channel| (channel.id.widen(), channel.base.last_message_id))
+            .chain(
+                guild
+                    .threads
+                    .iter()
+                    .map(|thread| (thread.id.widen(), thread.base.last_message_id)),
+            );
+        candidates.extend(
+            channels.filter_map(|(channel_id, last_message_id)| Some((*guild_id, channel_id, last_message_id?))),
+        );
+    }
+
+    let mut targets = Vec::new();
+    for (guild_id, channel_id, last_message_id) in candidates {
+        if channel_id == config.message_logging.snapshot_channel_id
+            || !cache_config.is_backfill_target(&channel_ancestor_ids(ctx, Some(guild_id), channel_id))
+        {
+            continue;
+        }
+
+        if let Some(after) = gap_start(ctx, gap_starts, channel_id).await
+            && last_message_id > after
+        {
+            targets.push((channel_id, after));
+        }
+    }
+
+    targets
+}
+
+/**
+ゲートウェイの切断中に送信されたメッセージを、新しいメッセージがあるチャンネルについてのみ取得します。
+*/
+pub(in crate::features::message_cache) async fn fill_gaps(ctx: &Context, controller: &BackfillController) {
+    let targets = gap_targets(ctx, &controller.take_gap_starts()).await;
+
+    controller.add_pending(targets.len());
+
+    let fetched_counts = stream::iter(targets)
+        .then(|target| async { (target, controller.acquire().await) })
+        .map(|((channel_id, after), permit)| async move {
+            controller.begin_channel(channel_id);
+            let result = fill_channel_gap(ctx, controller, channel_id, after).await;
+            drop(permit);
+
+            match result {
+                Ok(count) => {
+                    controller.finish_channel(channel_id, None);
+                    count
+                }
+                Err(error) => {
+                    warn!("Failed to fill message gap for channel {channel_id}: {error:#}");
+                    controller.finish_channel(channel_id, Some(error.to_string()));
+                    0
+                }
+            }
+        })
+        .buffer_unordered(MAX_CONCURRENT_CHANNELS)
+        .collect::<Vec<_>>()
+        .await;
+
+    info!(
+        "Filled message gaps after reconnect: {} messages",
+        fetched_counts.iter().sum::<usize>()
+    );
+}
@@ -0,0 +1,446 @@
+use std::{
+    iter,
+    pin::pin,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use chrono::Utc;
+use futures::{StreamExt, future};
+use serenity::{
+    all::{Channel, ChannelType, Context, Guild, GuildChannel, Member, prelude::CacheHttp},
+    async_trait,
+    http::RatelimitInfo,
+    model::{
+        Permissions,
+        channel::{GenericGuildChannelRef, GuildThread},
+        event::FullEvent,
+        id::{GenericChannelId, GuildId},
+    },
+    small_fixed_array::FixedString,
+};
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::{
+    app::{AppError, BotDataExt, config::MessageCacheConfig},
+    core::BotEventHandler,
+    features::message_cache::{
+        controller::{BackfillController, BackfillExt, BackfillTarget, MAX_CONCURRENT_CHANNELS},
+        gap_fill::{fill_gaps, newest_cached_message_ids},
+        progress_store::BackfillProgressStore,
+    },
+    utils::{channel_ancestor_ids, fetch_all_archived_public_thread},
+};
+
+enum ChannelWrapper {
+    Channel(GuildChannel),
+    Thread(GuildThread),
+}
+
+impl ChannelWrapper {
+    fn is_text_based(&self) -> bool {
+        match self {
+            Self::Channel(c) => c.is_text_based(),
+            Self::Thread(_) => true,
+        }
+    }
+
+    fn id(&self) -> GenericChannelId {
+        match self {
+            Self::Channel(c) => c.id.widen(),
+            Self::Thread(t) => t.id.widen(),
+        }
+    }
+
+    fn name(&self) -> FixedString<u16> {
+        (match self {
+            Self::Channel(c) => &c.base,
+            Self::Thread(t) => &t.base,
+        })
+        .clone()
+        .name
+    }
+
+    fn user_permission(&self, guild: &Guild, member: &Member) -> Option<Permissions> {
+        match self {
+            Self::Channel(c) => Some(guild.user_permissions_in(c, member)),
+            Self::Thread(t) => guild.channel(t.parent_id.into()).and_then(|c| match c {
+                GenericGuildChannelRef::Channel(gc) => Some(guild.user_permissions_in(gc, member)),
+                _ => None,
+            }),
+        }
+    }
+}
+
+pub struct MessageCacheHandler {
+    disabled: bool,
+    collected: AtomicBool,
+    /// レート制限の通知には Context が渡されないため、準備完了時に保持する
+    controller: OnceLock<Arc<BackfillController>>,
+}
+
+impl MessageCacheHandler {
+    pub fn new(disabled: bool) -> Self {
+        Self {
+            disabled,
+            collected: AtomicBool::new(false),
+            controller: OnceLock::new(),
+        }
+    }
+
+    async fn cache_channel_message(
+        ctx: &Context,
+        controller: &BackfillController,
+        channel: ChannelWrapper,
+        guild: &Guild,
+        bot_member: &Member,
+        config: &MessageCacheConfig,
+        force: bool,
+    ) {
+        if !channel.is_text_based() {
+            controller.skip_channel();
+            return;
+        }
+
+        let has_read_message_history_permission = channel
+            .user_permission(guild, bot_member)
+            .map(|p| p.read_message_history())
+            .unwrap_or(false);
+        if !has_read_message_history_permission {
+            controller.skip_channel();
+            return;
+        }
+
+        let requests_per_window: usize = config.requests_per_window.max(1).into();
+        let messages_per_window = requests_per_window.saturating_mul(100);
+        let database = ctx.database();
+        let channel_id = channel.id();
+        controller.begin_channel(channel_id);
+
+        let high_water_mark = if force {
+            None
+        } else {
+            match BackfillProgressStore::high_water_mark(&database, channel_id).await {
+                Ok(high_water_mark) => high_water_mark,
+                Err(error) => {
+                    error!("Failed to get backfill progress for channel {channel_id}: {error:#}");
+                    None
+                }
+            }
+        };
+        let recent_cutoff = (Utc::now() - config.recent_window).timestamp();
+        let age_cutoff = config.max_message_age.map(|max_age| (Utc::now() - max_age).timestamp());
+
+        // 一時停止中は最初のリクエストも送信しない
+        controller.wait_until_ready().await;
+
+        // Context を渡すと Serenity 標準キャッシュに取得結果が載るようになる
+        let mut messages = pin!(channel_id.messages_iter(ctx));
+        let mut newest_message_id = None;
+        let mut collected_count = 0;
+        let mut fetch_error = None;
+        while let Some(result) = messages.next().await {
+            let message = match result {
+                Ok(message) => message,
+                Err(error) => {
+                    warn!("Failed to fetch messages for channel {channel_id}: {error:#}");
+                    fetch_error = Some(error.to_string());
+                    break;
+                }
+            };
+
+            // 取得済みのメッセージに到達しても、直近の期間内はキャッシュのために取得を続ける
+            if high_water_mark.is_some_and(|id| message.id <= id) && message.timestamp.unix_timestamp() < recent_cutoff
+            {
+                break;
+            }
+
+            // 保持期間より古いメッセージはキャッシュしても削除されるため、それ以上遡らない
+            if age_cutoff.is_some_and(|cutoff| message.timestamp.unix_timestamp() < cutoff) {
+                break;
+            }
+
+            newest_message_id = newest_message_id.max(Some(message.id));
+            collected_count += 1;
+            controller.add_cached_messages(1);
+            if collected_count % messages_per_window == 0 {
+                controller.wait_request_window(config.request_window).await;
+            }
+            controller.wait_until_ready().await;
+        }
+
+        // 途中で失敗した場合は未取得のメッセージが残るため記録しない
+        if fetch_error.is_none()
+            && let Some(newest_message_id) = newest_message_id
+            && let Err(error) =
+                BackfillProgressStore::update_high_water_mark(&database, channel_id, newest_message_id).await
+        {
+            error!("Failed to save backfill progress for channel {channel_id}: {error:#}");
+        }
+
+        controller.finish_channel(channel_id, fetch_error);
+        info!(
+            "Cached {collected_count} messages for channel: {} ({channel_id})",
+            channel.name(),
+        );
+    }
+
+    async fn fetch_bot_member(ctx: &Context, guild_id: GuildId) -> Option<(Guild, Member)> {
+        let guild = match guild_id.to_guild_cached(&ctx.cache) {
+            Some(guild) => guild.clone(),
+            None => {
+                error!("Failed to get guild: {}", guild_id);
+                return None;
+            }
+        };
+
+        let bot_id = ctx.cache.current_user().id;
+
+        let Ok(bot_member) = guild.member(ctx.http(), bot_id).await else {
+            error!("Failed to get bot member for guild: {}", guild_id);
+            return None;
+        };
+
+        Some((guild, bot_member))
+    }
+
+    async fn cache_guild_messages(ctx: &Context, controller: &BackfillController, guild_id: GuildId, force: bool) {
+        let config = ctx.app_config().await;
+        let ignore_channel_ids = [config.message_logging.snapshot_channel_id];
+
+        let Some((guild, bot_member)) = Self::fetch_bot_member(ctx, guild_id).await else {
+            return;
+        };
+
+        let Ok(channels) = guild_id.channels(ctx.http()).await else {
+            error!("Failed to get channels for guild: {}", guild_id);
+            return;
+        };
+
+        // アーカイブされたスレッドはキャッシュにないため、親チャンネルから辿る
+        let thread_ancestor_ids = |thread: &GuildThread| {
+            iter::once(thread.id.widen())
+                .chain(channel_ancestor_ids(ctx, Some(guild_id), thread.parent_id.widen()))
+                .collect::<Vec<_>>()
+        };
+        let cache_config = &config.message_cache;
+        let archived_cutoff = cache_config
+            .archived_thread_max_age
+            .map(|max_age| (Utc::now() - max_age).timestamp());
+
+        // アーカイブされたスレッドの一覧の取得を待たずに開始するため、見つかった順に取得する
+        let active_threads = guild.threads.clone();
+        let targets = async_stream::stream! {
+            for thread in active_threads {
+                if cache_config.is_backfill_target(&thread_ancestor_ids(&thread)) {
+                    controller.add_pending(1);
+                    yield ChannelWrapper::Thread(thread);
+                }
+            }
+
+            for channel in channels {
+                let id = channel.id;
+
+                if ignore_channel_ids.contains(&id.widen())
+                    || !cache_config.is_backfill_target(&channel_ancestor_ids(ctx, Some(guild_id), id.widen()))
+                {
+                    continue;
+                }
+
+                let thread_limit = if channel.base.kind == ChannelType::Forum {
+                    cache_config.forum_thread_limit_for(id)
+                } else {
+                    None
+                };
+                controller.add_pending(1);
+                yield ChannelWrapper::Channel(channel);
+
+                // 一時停止中はアーカイブされたスレッドの一覧も取得しない
+                controller.wait_until_ready().await;
+
+                // アーカイブされた日時の新しい順に取得されるため、条件を満たさなくなった時点で打ち切る
+                let archived_threads = fetch_all_archived_public_thread(ctx, id, None)
+                    .await
+                    .take_while(|thread| {
+                        let archive_timestamp = thread.thread_metadata.archive_timestamp;
+                        future::ready(archived_cutoff.is_none_or(|cutoff| {
+                            archive_timestamp.is_none_or(|timestamp| timestamp.unix_timestamp() >= cutoff)
+                        }))
+                    })
+                    .take(thread_limit.unwrap_or(usize::MAX));
+                for await thread in archived_threads {
+                    if cache_config.is_backfill_target(&thread_ancestor_ids(&thread)) {
+                        controller.add_pending(1);
+                        yield ChannelWrapper::Thread(thread);
+                    }
+                }
+            }
+        };
+
+        let _ = targets
+            .then(|c| async { (c, controller.acquire().await) })
+            .map(|(c, permit)| {
+                let (guild, bot_member, config) = (&guild, &bot_member, &config);
+                async move {
+                    Self::cache_channel_message(ctx, controller, c, guild, bot_member, &config.message_cache, force)
+                        .await;
+                    drop(permit);
+                }
+            })
+            .buffer_unordered(MAX_CONCURRENT_CHANNELS)
+            .collect::<Vec<_>>()
+            .await;
+    }
+
+    async fn recache_channel(ctx: &Context, controller: &BackfillController, channel_id: GenericChannelId) {
+        let config = ctx.app_config().await;
+
+        let (channel, guild_id) = match ctx.http.get_channel(channel_id).await {
+            Ok(Channel::Guild(channel)) => {
+                let guild_id = channel.base.guild_id;
+                (ChannelWrapper::Channel(channel), guild_id)
+            }
+            Ok(Channel::GuildThread(thread)) => {
+                let guild_id = thread.base.guild_id;
+                (ChannelWrapper::Thread(thread), guild_id)
+            }
+            Ok(_) => {
+                error!("Channel is not a guild channel: {channel_id}");
+                return;
+            }
+            Err(error) => {
+                error!("Failed to get channel {channel_id}: {error:#}");
+                return;
+            }
+        };
+
+        let Some((guild, bot_member)) = Self::fetch_bot_member(ctx, guild_id).await else {
+            return;
+        };
+
+        controller.add_pending(1);
+        let permit = controller.acquire().await;
+        Self::cache_channel_message(
+            ctx,
+            controller,
+            channel,
+            &guild,
+            &bot_member,
+            &config.message_cache,
+            true,
+        )
+        .await;
+        drop(permit);
+    }
+
+    async fn collect_cache(ctx: &Context, controller: &BackfillController) {
+        let config = ctx.app_config().await;
+
+        controller.start();
+        for guild_id in &config.message_cache.target_guild_ids {
+            Self::cache_guild_messages(ctx, controller, *guild_id, false).await;
+        }
+        controller.finish();
+        info!("Cache ready!");
+    }
+
+    /**
+    起動時の過去メッセージの取得を行い、その後はコマンドや再接続による再取得の要求を処理します。
+    */
+    async fn run_backfill(ctx: Context, controller: Arc<BackfillController>, disabled: bool) {
+        let Some(mut requests) = controller.take_request_receiver() else {
+            return;
+        };
+
+        let config = ctx.app_config().await;
+        controller.init_concurrent_channels(config.message_cache.concurrent_channels.into());
+
+        if !disabled {
+            Self::collect_cache(&ctx, &controller).await;
+        }
+
+        while let Some(target) = requests.recv().await {
+            controller.start();
+            match target {
+                BackfillTarget::Channel(channel_id) => Self::recache_channel(&ctx, &controller, channel_id).await,
+                BackfillTarget::Guild(guild_id) => Self::cache_guild_messages(&ctx, &controller, guild_id, true).await,
+                BackfillTarget::Gaps => fill_gaps(&ctx, &controller).await,
+            }
+            controller.finish();
+            info!("Backfill finished: {target:?}");
+        }
+    }
+
+    async fn handle_cache_ready(&self, ctx: &Context) {
+        if self.collected.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let controller = match ctx.backfill() {
+            Ok(controller) => controller,
+            Err(error) => {
+                error!("Failed to start message backfill: {error:#}");
+                return;
+            }
+        };
+        let _ = self.controller.set(controller.clone());
+
+        // 過去メッセージを取得しない場合も、受信したメッセージはキャッシュされるため削除は行う
+        tokio::spawn(Self::run_eviction_loop(ctx.clone(), controller.clone()));
+        tokio::spawn(Self::run_backfill(ctx.clone(), controller, self.disabled));
+    }
+
+    /**
+    キャッシュの準備完了後に再接続した場合、切断中のメッセージの取得を要求します。
+    */
+    async fn handle_reconnect(&self, ctx: &Context) {
+        let Some(controller) = self.controller.get() else {
+            return;
+        };
+
+        // 取得は他の取得が終わるまで待たされるため、再接続時点の最新のメッセージを先に記録する
+        let config = ctx.app_config().await;
+        controller.record_gap_starts(newest_cached_message_ids(ctx, &config.message_cache.target_guild_ids));
+
+        info!("Gateway reconnected, requesting message gap fill");
+        controller.request(BackfillTarget::Gaps);
+    }
+
+    async fn run_eviction_loop(ctx: Context, controller: Arc<BackfillController>) {
+        loop {
+            let config = ctx.app_config().await;
+            let eviction_interval = config.message_cache.eviction_interval;
+
+            if let Some(max_age) = config.message_cache.max_message_age {
+                let evicted_count = evict_expired_messages(&ctx, &controller.cached_channel_ids(), max_age);
+                if evicted_count > 0 {
+                    info!("Evicted {evicted_count} expired messages from cache");
+                }
+            }
+
+            sleep(eviction_interval).await;
+        }
+    }
+}
+
+#[async_trait]
+impl BotEventHandler for MessageCacheHandler {
+    async fn dispatch(&self, ctx: &Context, event: &FullEvent) -> Result<(), AppError> {
+        match event {
+            FullEvent::CacheReady { .. } => self.handle_cache_ready(ctx).await,
+            FullEvent::Ready { .. } | FullEvent::Resume { .. } => self.handle_reconnect(ctx).await,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    async fn ratelimit(&self, data: &RatelimitInfo) {
+        if let Some(controller) = self.controller.get() {
+            controller.on_ratelimit(data.timeout);
+        }
+    }
+}
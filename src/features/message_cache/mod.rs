@@ -0,0 +1,11 @@
+mod command;
+mod controller;
+mod eviction;
+mod gap_fill;
+mod handler;
+mod progress_store;
+mod throttle;
+
+pub use command::backfill;
+pub use controller::BackfillController;
+pub use handler::MessageCacheHandler;
@@ -0,0 +1,48 @@
+use serenity::model::id::{GenericChannelId, MessageId};
+use sqlx::PgPool;
+
+use crate::app::AppError;
+
+/**
+チャンネルごとに取得済みの最新メッセージ (ハイウォーターマーク) を永続化します。
+
+再起動時はこれより新しいメッセージのみを取得すれば良いため、全履歴の再取得を避けられます。
+*/
+pub(in crate::features::message_cache) struct BackfillProgressStore;
+
+impl BackfillProgressStore {
+    pub async fn high_water_mark(
+        database: &PgPool,
+        channel_id: GenericChannelId,
+    ) -> Result<Option<MessageId>, AppError> {
+        let row: Option<(i64,)> =
+            sqlx::query_as("SELECT newest_message_id FROM message_cache_progress WHERE channel_id = $1")
+                .bind(channel_id.get() as i64)
+                .fetch_optional(database)
+                .await?;
+
+        Ok(row.map(|(id,)| MessageId::new(id as u64)))
+    }
+
+    /**
+    取得済みの最新メッセージを更新します。既に記録されているものより古い場合は何もしません。
+    */
+    pub async fn update_high_water_mark(
+        database: &PgPool,
+        channel_id: GenericChannelId,
+        message_id: MessageId,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO message_cache_progress (channel_id, newest_message_id) VALUES ($1, $2)
+            ON CONFLICT (channel_id) DO UPDATE SET
+                newest_message_id = GREATEST(message_cache_progress.newest_message_id, EXCLUDED.newest_message_id),
+                updated_at = now()",
+        )
+        .bind(channel_id.get() as i64)
+        .bind(message_id.get() as i64)
+        .execute(database)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// 取得間隔を広げる段階の上限 (段階ごとに同時実行数を半分、待機時間を 2 倍にする)
+const MAX_THROTTLE_LEVEL: u32 = 4;
+
+/// この期間レート制限を受けなければ段階を 1 つ下げる
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(60);
+
+struct ThrottleState {
+    level: u32,
+    changed_at: Instant,
+    backoff_until: Option<Instant>,
+}
+
+/**
+レート制限の発生に応じて過去メッセージの取得ペースを段階的に落とし、発生しなくなれば戻します。
+*/
+pub(in crate::features::message_cache) struct AdaptiveThrottle {
+    state: Mutex<ThrottleState>,
+}
+
+impl AdaptiveThrottle {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(ThrottleState {
+                level: 0,
+                changed_at: Instant::now(),
+                backoff_until: None,
+            }),
+        }
+    }
+
+    pub fn level(&self) -> u32 {
+        self.state.lock().unwrap().level
+    }
+
+    /**
+    レート制限を記録して段階を上げ、解除されるまでの待機を設定します。段階が変化したかどうかを返します。
+    */
+    pub fn on_ratelimit(&self, timeout: Duration) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let backoff_until = now + timeout;
+        state.backoff_until = Some(
+            state
+                .backoff_until
+                .map_or(backoff_until, |until| until.max(backoff_until)),
+        );
+
+        let previous = state.level;
+        state.level = (state.level + 1).min(MAX_THROTTLE_LEVEL);
+        state.changed_at = now;
+
+        state.level != previous
+    }
+
+    /**
+    最後の変化から一定期間レート制限を受けていなければ段階を下げます。段階が変化したかどうかを返します。
+    */
+    pub fn recover(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.level == 0 || state.changed_at.elapsed() < RECOVERY_INTERVAL {
+            return false;
+        }
+
+        state.level -= 1;
+        state.changed_at = Instant::now();
+        true
+    }
+
+    /**
+    レート制限の解除を待つ必要がある場合、その時刻を返します。
+    */
+    pub fn backoff_until(&self) -> Option<Instant> {
+        let state = self.state.lock().unwrap();
+        state.backoff_until.filter(|until| *until > Instant::now())
+    }
+
+    /**
+    現在の段階に応じて広げた待機時間を返します。
+    */
+    pub fn scale_delay(&self, delay: Duration) -> Duration {
+        delay * 2u32.pow(self.level())
+    }
+
+    /**
+    現在の段階に応じて絞った同時実行数を返します。
+    */
+    pub fn scale_concurrency(&self, concurrency: usize) -> usize {
+        (concurrency >> self.level()).max(1)
+    }
+}
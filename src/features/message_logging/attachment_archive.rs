@@ -0,0 +1,379 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::Context as _;
+use chrono::{Duration, Utc};
+use serenity::{
+    all::prelude::Context,
+    builder::CreateAttachment,
+    model::{
+        channel::Message,
+        id::{AttachmentId, GenericChannelId, MessageId},
+    },
+};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::{fs, sync::OnceCell};
+use tracing::{debug, warn};
+
+use crate::{
+    app::{AppError, BotDataExt, config::LocalArchiveConfig},
+    features::message_logging::snapshot_store::snowflake_upper_bound,
+};
+
+/**
+添付ファイルの内容のハッシュから保存先のパスを求めます。
+*/
+fn content_path(directory: &Path, content_hash: &str) -> PathBuf {
+    directory.join(&content_hash[..2]).join(content_hash)
+}
+
+/**
+添付ファイルをローカルのディレクトリに内容のハッシュをファイル名として保存します。
+
+同じ内容のファイルは 1 つだけ保存され、どのメッセージからも参照されなくなった時点で削除されます。
+全体のサイズの上限に達した場合は、古いメッセージの添付ファイルから削除して空きを作ります。
+*/
+pub(in crate::features::message_logging) struct LocalAttachmentArchive {
+    /// 保存されているファイルの合計サイズ (初回の使用時にデータベースから集計し、以降は増減を反映する)
+    total_size: OnceCell<AtomicU64>,
+}
+
+impl LocalAttachmentArchive {
+    pub fn new() -> Self {
+        Self {
+            total_size: OnceCell::new(),
+        }
+    }
+
+    async fn query_total_size(database: &PgPool) -> Result<u64, AppError> {
+        let (size,): (i64,) = sqlx::query_as(
+            "SELECT COALESCE(SUM(size), 0)::BIGINT FROM (
+                SELECT DISTINCT content_hash, size FROM archived_attachments
+            ) contents",
+        )
+        .fetch_one(database)
+        .await?;
+
+        Ok(size as u64)
+    }
+
+    async fn total_size(&self, database: &PgPool) -> Result<&AtomicU64, AppError> {
+        self.total_size
+            .get_or_try_init(|| async { Self::query_total_size(database).await.map(AtomicU64::new) })
+            .await
+    }
+
+    async fn archived_attachment_ids(
+        database: &PgPool,
+        channel_id: GenericChannelId,
+        message_id: MessageId,
+    ) -> Result<Vec<AttachmentId>, AppError> {
+        let rows: Vec<(i64,)> =
+            sqlx::query_as("SELECT attachment_id FROM archived_attachments WHERE channel_id = $1 AND message_id = $2")
+                .bind(channel_id.get() as i64)
+                .bind(message_id.get() as i64)
+                .fetch_all(database)
+                .await?;
+
+        Ok(rows.into_iter().map(|(id,)| AttachmentId::new(id as u64)).collect())
+    }
+
+    /**
+    全体のサイズに `required` を加えても上限を超えないよう、古いメッセージの添付ファイルから削除します。
+
+    `keep` のメッセージは削除の対象外とし、削除したメッセージの件数を返します。
+    削除できるものが無くなった場合は、上限を超えたまま終了します。
+    */
+    async fn evict_to_fit(
+        &self,
+        ctx: &Context,
+        config: &LocalArchiveConfig,
+        keep: Option<&Message>,
+        required: u64,
+    ) -> Result<usize, AppError> {
+        let database = ctx.database();
+        let total_size = self.total_size(&database).await?;
+        let mut evicted_count = 0;
+
+        while total_size.load(Ordering::Relaxed) + required > config.max_total_size {
+            let oldest: Option<(i64, i64)> = sqlx::query_as(
+                "SELECT channel_id, message_id FROM archived_attachments
+                WHERE $1::BIGINT IS NULL OR NOT (channel_id = $1 AND message_id = $2)
+                ORDER BY message_id LIMIT 1",
+            )
+            .bind(keep.map(|message| message.channel_id.get() as i64))
+            .bind(keep.map(|message| message.id.get() as i64))
+            .fetch_optional(&database)
+            .await?;
+
+            let Some((channel_id, message_id)) = oldest else {
+                break;
+            };
+
+            let message_id = MessageId::new(message_id as u64);
+            self.delete_attachments(ctx, config, GenericChannelId::new(channel_id as u64), message_id, None)
+                .await?;
+            debug!("Evicted archived attachments of message {message_id} to free space");
+
+            evicted_count += 1;
+        }
+
+        Ok(evicted_count)
+    }
+
+    /**
+    メッセージの添付ファイルを保存し、既に無くなった添付ファイルの記録を削除します。
+
+    サイズの上限を超える添付ファイルや、ダウンロードに失敗した添付ファイルは保存しません。
+    */
+    pub async fn store(&self, ctx: &Context, config: &LocalArchiveConfig, message: &Message) -> Result<(), AppError> {
+        let database = ctx.database();
+        let archived_ids = Self::archived_attachment_ids(&database, message.channel_id, message.id).await?;
+
+        let removed_ids = archived_ids
+            .iter()
+            .filter(|id| !message.attachments.iter().any(|attachment| attachment.id == **id))
+            .copied()
+            .collect::<Vec<_>>();
+        if !removed_ids.is_empty() {
+            self.delete_attachments(ctx, config, message.channel_id, message.id, Some(&removed_ids))
+                .await?;
+        }
+
+        let total_size = self.total_size(&database).await?;
+
+        for attachment in message.attachments.iter().filter(|a| !archived_ids.contains(&a.id)) {
+            let size = u64::from(attachment.size);
+            let max_file_size = config.max_file_size_for(attachment.content_type.as_deref());
+            if size > max_file_size {
+                debug!("Skipped archiving attachment {} ({size} bytes)", attachment.id);
+                continue;
+            }
+            // 上限を超える場合は古いものを削除して空きを作る
+            if total_size.load(Ordering::Relaxed) + size > config.max_total_size && size <= config.max_total_size {
+                self.evict_to_fit(ctx, config, Some(message), size).await?;
+            }
+            if total_size.load(Ordering::Relaxed) + size > config.max_total_size {
+                warn!(
+                    "Skipped archiving attachment {}: archive size limit reached ({} bytes)",
+                    attachment.id,
+                    total_size.load(Ordering::Relaxed)
+                );
+                continue;
+            }
+
+            // 1 つのダウンロードに失敗しても、残りの添付ファイルは保存する
+            let data = match attachment.download().await {
+                Ok(data) => data,
+                Err(error) => {
+                    warn!("Failed to download attachment {} for archive: {error}", attachment.id);
+                    continue;
+                }
+            };
+            let content_hash = format!("{:x}", Sha256::digest(&data));
+
+            // 同じ内容のファイルが保存されていない場合のみ書き込む
+            let path = content_path(&config.directory, &content_hash);
+            if !fs::try_exists(&path).await? {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                fs::write(&path, &data)
+                    .await
+                    .with_context(|| format!("Failed to write archived attachment: {}", path.display()))?;
+                total_size.fetch_add(size, Ordering::Relaxed);
+            }
+
+            sqlx::query(
+                "INSERT INTO archived_attachments
+                    (channel_id, message_id, attachment_id, filename, content_type, size, content_hash)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (channel_id, message_id, attachment_id) DO NOTHING",
+            )
+            .bind(message.channel_id.get() as i64)
+            .bind(message.id.get() as i64)
+            .bind(attachment.id.get() as i64)
+            .bind(attachment.filename.to_string())
+            .bind(attachment.content_type.as_deref().map(str::to_string))
+            .bind(size as i64)
+            .bind(content_hash)
+            .execute(&database)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /**
+    保存された添付ファイルを読み込み、添付ファイルのIDごとに返します。
+
+    サイズの上限などで保存されなかったものや、読み込みに失敗したものは含まれません。
+    */
+    pub async fn load(
+        &self,
+        ctx: &Context,
+        config: &LocalArchiveConfig,
+        message: &Message,
+    ) -> Result<HashMap<AttachmentId, CreateAttachment<'static>>, AppError> {
+        let rows: Vec<(i64, String, String)> = sqlx::query_as(
+            "SELECT attachment_id, filename, content_hash FROM archived_attachments
+            WHERE channel_id = $1 AND message_id = $2",
+        )
+        .bind(message.channel_id.get() as i64)
+        .bind(message.id.get() as i64)
+        .fetch_all(&ctx.database())
+        .await?;
+
+        let mut attachments = HashMap::new();
+        for (attachment_id, filename, content_hash) in rows {
+            let path = content_path(&config.directory, &content_hash);
+            match fs::read(&path).await {
+                Ok(data) => {
+                    attachments.insert(
+                        AttachmentId::new(attachment_id as u64),
+                        CreateAttachment::bytes(data, filename),
+                    );
+                }
+                Err(error) => warn!("Failed to read archived attachment {}: {error}", path.display()),
+            }
+        }
+
+        Ok(attachments)
+    }
+
+    /**
+    保持期間を過ぎたメッセージと、チャンネルごとの上限数を超えた古いメッセージの添付ファイルを削除し、
+    対象となったメッセージの件数を返します。全体のサイズが上限を超えている場合は古いものから削除します。
+    */
+    pub async fn prune(
+        &self,
+        ctx: &Context,
+        config: &LocalArchiveConfig,
+        max_age: Option<Duration>,
+        max_count_per_channel: Option<u32>,
+    ) -> Result<usize, AppError> {
+        let database = ctx.database();
+        let mut expired: Vec<(i64, i64)> = Vec::new();
+
+        if let Some(max_age) = max_age {
+            expired.extend(
+                sqlx::query_as::<_, (i64, i64)>(
+                    "SELECT DISTINCT channel_id, message_id FROM archived_attachments WHERE message_id < $1",
+                )
+                .bind(snowflake_upper_bound(Utc::now() - max_age))
+                .fetch_all(&database)
+                .await?,
+            );
+        }
+
+        if let Some(max_count) = max_count_per_channel {
+            expired.extend(
+                sqlx::query_as::<_, (i64, i64)>(
+                    "SELECT channel_id, message_id FROM (
+                        SELECT channel_id, message_id,
+                            DENSE_RANK() OVER (PARTITION BY channel_id ORDER BY message_id DESC) AS rank
+                        FROM archived_attachments
+                    ) ranked WHERE rank > $1",
+                )
+                .bind(i64::from(max_count))
+                .fetch_all(&database)
+                .await?,
+            );
+        }
+
+        expired.sort_unstable();
+        expired.dedup();
+
+        let mut pruned_count = expired.len();
+        for (channel_id, message_id) in expired {
+            self.delete_attachments(
+                ctx,
+                config,
+                GenericChannelId::new(channel_id as u64),
+                MessageId::new(message_id as u64),
+                None,
+            )
+            .await?;
+        }
+
+        // 上限が引き下げられた場合に備えて、全体のサイズも確認する
+        let evicted_count = self.evict_to_fit(ctx, config, None, 0).await?;
+        pruned_count += evicted_count;
+
+        Ok(pruned_count)
+    }
+
+    pub async fn delete(
+        &self,
+        ctx: &Context,
+        config: &LocalArchiveConfig,
+        channel_id: GenericChannelId,
+        message_id: MessageId,
+    ) -> Result<(), AppError> {
+        self.delete_attachments(ctx, config, channel_id, message_id, None).await
+    }
+
+    /**
+    メッセージの添付ファイルの記録を削除し、どこからも参照されなくなったファイルを削除します。
+
+    `attachment_ids` が `None` の場合はメッセージの全ての添付ファイルを対象とします。
+    */
+    async fn delete_attachments(
+        &self,
+        ctx: &Context,
+        config: &LocalArchiveConfig,
+        channel_id: GenericChannelId,
+        message_id: MessageId,
+        attachment_ids: Option<&[AttachmentId]>,
+    ) -> Result<(), AppError> {
+        let database = ctx.database();
+        let attachment_ids = attachment_ids.map(|ids| ids.iter().map(|id| id.get() as i64).collect::<Vec<_>>());
+
+        let mut deleted_hashes: Vec<(String, i64)> = sqlx::query_as(
+            "DELETE FROM archived_attachments
+            WHERE channel_id = $1 AND message_id = $2 AND ($3::BIGINT[] IS NULL OR attachment_id = ANY($3))
+            RETURNING content_hash, size",
+        )
+        .bind(channel_id.get() as i64)
+        .bind(message_id.get() as i64)
+        .bind(attachment_ids)
+        .fetch_all(&database)
+        .await?;
+
+        // 同じ内容の添付ファイルが複数ある場合も、ファイルとサイズは 1 つ分として扱う
+        deleted_hashes.sort_unstable();
+        deleted_hashes.dedup();
+
+        for (content_hash, size) in deleted_hashes {
+            let (referenced,): (bool,) =
+                sqlx::query_as("SELECT EXISTS (SELECT 1 FROM archived_attachments WHERE content_hash = $1)")
+                    .bind(&content_hash)
+                    .fetch_one(&database)
+                    .await?;
+            if referenced {
+                continue;
+            }
+
+            // 合計サイズはデータベースの記録から求めるため、ファイルの削除の成否によらず減らす
+            if let Some(total_size) = self.total_size.get() {
+                let _ = total_size.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                    Some(total.saturating_sub(size as u64))
+                });
+            }
+
+            let path = content_path(&config.directory, &content_hash);
+            if let Err(error) = fs::remove_file(&path).await
+                && error.kind() != ErrorKind::NotFound
+            {
+                warn!("Failed to remove archived attachment {}: {error}", path.display());
+            }
+        }
+
+        Ok(())
+    }
+}
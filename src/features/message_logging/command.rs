@@ -0,0 +1,106 @@
+use poise::say_reply;
+use serenity::all::MessageBuilder;
+
+use crate::{
+    app::{AppContext, AppError, BotDataExt},
+    extensions::MessageBuilderTimestampExt,
+    features::message_logging::snapshot_store::SnapshotStoreExt,
+    utils::format_duration,
+};
+
+/// 添付ファイルのスナップショットを管理
+#[poise::command(
+    slash_command,
+    ephemeral,
+    owners_only,
+    dm_only,
+    subcommands("snapshot_status", "snapshot_prune"),
+    subcommand_required
+)]
+pub async fn snapshot(_ctx: AppContext<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// スナップショットの保存状況を表示
+#[poise::command(slash_command, ephemeral, owners_only, dm_only, rename = "status")]
+pub async fn snapshot_status(ctx: AppContext<'_>) -> Result<(), AppError> {
+    ctx.defer_ephemeral().await?;
+
+    let config = ctx.app_config().await;
+    let stats = ctx.snapshot_store()?.stats(ctx.serenity_context()).await?;
+
+    let mut builder = MessageBuilder::new()
+        .push_bold("スナップショット数: ")
+        .push_line(format!("{}件 (チャンネル数: {})", stats.total_count, stats.channel_count).as_str());
+
+    if let Some(timestamp) = stats.oldest_snapshot_timestamp() {
+        builder = builder
+            .push_bold("最古のスナップショット: ")
+            .push_short_date_medium_timestamp_line(timestamp);
+    }
+
+    builder = builder.push_bold("保持設定: ");
+    builder = match &config.message_logging.snapshot_retention {
+        Some(retention) => builder.push_line(
+            format!(
+                "保持期間 {} / チャンネルあたり最大 {}",
+                retention
+                    .max_age
+                    .and_then(|max_age| max_age.to_std().ok())
+                    .map_or("無期限".to_string(), |max_age| format_duration(max_age, 2)),
+                retention
+                    .max_count_per_channel
+                    .map_or("無制限".to_string(), |count| format!("{count}件")),
+            )
+            .as_str(),
+        ),
+        None => builder.push_line("無期限"),
+    };
+
+    if !stats.largest_channels.is_empty() {
+        builder = builder.push_bold_line("件数の多いチャンネル:");
+        for (channel_id, count) in &stats.largest_channels {
+            builder = builder.push_line(format!("- <#{channel_id}> `{channel_id}`: {count}件").as_str());
+        }
+    }
+
+    say_reply(ctx, builder.build()).await?;
+    Ok(())
+}
+
+/// 保持設定に従ってスナップショットを削除
+#[poise::command(slash_command, ephemeral, owners_only, dm_only, rename = "prune")]
+pub async fn snapshot_prune(
+    ctx: AppContext<'_>,
+    #[description = "この期間より古いスナップショットを削除 (例: 30d, 省略時は設定値)"] max_age: Option<String>,
+) -> Result<(), AppError> {
+    let config = ctx.app_config().await;
+    let retention = config.message_logging.snapshot_retention.as_ref();
+
+    let max_age = match max_age {
+        Some(max_age) => match duration_str::parse_chrono(&max_age) {
+            Ok(max_age) => Some(max_age),
+            Err(_) => {
+                say_reply(ctx, format!("期間 `{max_age}` の解析に失敗しました。")).await?;
+                return Ok(());
+            }
+        },
+        None => retention.and_then(|retention| retention.max_age),
+    };
+    let max_count_per_channel = retention.and_then(|retention| retention.max_count_per_channel);
+
+    if max_age.is_none() && max_count_per_channel.is_none() {
+        say_reply(ctx, "保持設定が無いため、削除対象がありません。").await?;
+        return Ok(());
+    }
+
+    ctx.defer_ephemeral().await?;
+
+    let pruned_count = ctx
+        .snapshot_store()?
+        .prune(ctx.serenity_context(), max_age, max_count_per_channel)
+        .await?;
+
+    say_reply(ctx, format!("{pruned_count}件のスナップショットを削除しました。")).await?;
+    Ok(())
+}
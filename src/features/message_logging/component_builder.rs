@@ -9,14 +9,14 @@ use crate::{
 };
 use itertools::{Either, Itertools, enumerate};
 use serenity::{
-    all::{Message, MessageBuilder, MessageReferenceKind},
+    all::{Mentionable, Message, MessageBuilder, MessageReferenceKind, Timestamp},
     builder::{
         CreateContainerComponent, CreateFile, CreateMediaGallery, CreateMediaGalleryItem, CreateSectionComponent,
         CreateUnfurledMediaItem,
     },
     model::{
-        channel::{Attachment, MessageFlags, MessageReference, MessageType},
-        id::AttachmentId,
+        channel::{Attachment, Embed, MessageFlags, MessageReference, MessageType, Poll},
+        id::{AttachmentId, GenericChannelId},
     },
     utils::{Content, ContentModifier},
 };
@@ -87,8 +87,32 @@ fn build_reference_title_and_body(message: &Message, message_reference: &Message
     ("参照", format_message_link_line("参照元", message_reference))
 }
 
+/**
+参照先のメッセージの内容を引用として追加します。
+
+長い内容は `QUOTE_MAX_CHARS` 文字で切り詰めます。
+*/
+fn push_quoted_content(mut builder: MessageBuilder, content: &str, attachment_count: usize) -> MessageBuilder {
+    const QUOTE_MAX_CHARS: usize = 500;
+
+    let mut excerpt = content.chars().take(QUOTE_MAX_CHARS).collect::<String>();
+    if content.chars().count() > QUOTE_MAX_CHARS {
+        excerpt.push('…');
+    }
+
+    for line in excerpt.lines() {
+        builder = builder.push_quote_line_safe(line);
+    }
+    if attachment_count > 0 {
+        builder = builder.push_quote_line_safe(format!("<添付ファイル {attachment_count}件>").as_str());
+    }
+
+    builder
+}
+
 pub(in crate::features::message_logging) fn build_message_reference_container_component<'a>(
     message: &Message,
+    referenced_message: Option<&Message>,
 ) -> Option<CreateContainerComponent<'a>> {
     let message_reference = message.message_reference.as_ref()?;
 
@@ -98,23 +122,41 @@ pub(in crate::features::message_logging) fn build_message_reference_container_co
         _ => ("不明", "不明なメッセージ参照: ".to_string()),
     };
 
-    Some(create_container_text(
-        MessageBuilder::new()
-            .push("### ")
-            .push_line(bold_underline(name))
-            .push_line(content.as_str())
-            .build(),
-    ))
-}
+    let mut builder = MessageBuilder::new()
+        .push("### ")
+        .push_line(bold_underline(name))
+        .push_line(content.as_str());
+
+    match message_reference.kind {
+        MessageReferenceKind::Default => {
+            if let Some(referenced_message) = referenced_message {
+                builder = builder
+                    .push_bold_safe("送信者: ")
+                    .mention(&referenced_message.author.mention())
+                    .push_safe(" ")
+                    .push_mono_line_safe(&*referenced_message.author.id.to_string());
+                builder = push_quoted_content(
+                    builder,
+                    &referenced_message.content,
+                    referenced_message.attachments.len(),
+                );
+            }
+        }
+        MessageReferenceKind::Forward => {
+            for snapshot in &message.message_snapshots {
+                builder = push_quoted_content(builder, &snapshot.content, snapshot.attachments.len());
+            }
+        }
+        _ => {}
+    }
 
-pub(in crate::features::message_logging) fn build_poll_container_component<'a>(
-    message: &Message,
-) -> Option<CreateContainerComponent<'a>> {
-    let poll = message.poll.as_ref()?;
+    Some(create_container_text(builder.build()))
+}
 
+fn format_poll(title: &str, poll: &Poll) -> Option<String> {
     let mut builder = MessageBuilder::new()
         .push("### ")
-        .push_line(bold_underline("投票"))
+        .push_line(bold_underline(title))
         .push_bold_safe("タイトル: ")
         .push_line_safe(poll.question.text.as_deref().unwrap_or("<不明なタイトル>"))
         .push_bold_line_safe("回答:");
@@ -138,7 +180,105 @@ pub(in crate::features::message_logging) fn build_poll_container_component<'a>(
             .push_short_date_medium_timestamp(expiry);
     }
 
-    Some(create_container_text(builder.build()))
+    Some(builder.build())
+}
+
+pub(in crate::features::message_logging) fn build_poll_container_component<'a>(
+    message: &Message,
+) -> Option<CreateContainerComponent<'a>> {
+    let poll = message.poll.as_ref()?;
+
+    Some(create_container_text(format_poll("投票", poll)?))
+}
+
+fn format_embed_line(embed: &Embed) -> String {
+    let title = embed
+        .title
+        .as_deref()
+        .or(embed.url.as_deref())
+        .unwrap_or("<無題の埋め込み>");
+
+    match &embed.url {
+        Some(url) => format!("- [{title}](<{url}>)"),
+        None => format!("- {title}"),
+    }
+}
+
+fn format_titled_lines(title: &str, lines: impl Iterator<Item = String>) -> Option<String> {
+    let lines = lines.collect_vec();
+    if lines.is_empty() {
+        return None;
+    }
+
+    Some(
+        MessageBuilder::new()
+            .push("### ")
+            .push_line(bold_underline(title))
+            .push(lines.join("\n").as_str())
+            .build(),
+    )
+}
+
+/**
+内容以外の変更について、変更された項目を表示するコンポーネントを作成します。
+*/
+pub(in crate::features::message_logging) fn build_change_container_component<'a>(
+    message: &Message,
+    log_kind: &MessageLogKind,
+) -> Option<CreateContainerComponent<'a>> {
+    let content = match log_kind {
+        MessageLogKind::EmbedRemoved { embeds_after } => format_titled_lines(
+            "削除された埋め込み",
+            message
+                .embeds
+                .iter()
+                .filter(|embed| {
+                    !embeds_after
+                        .iter()
+                        .any(|after| after.url == embed.url && after.title == embed.title)
+                })
+                .map(format_embed_line),
+        )?,
+        MessageLogKind::EmbedsSuppressed => {
+            format_titled_lines("非表示にされた埋め込み", message.embeds.iter().map(format_embed_line))?
+        }
+        MessageLogKind::StickerRemoved { stickers_after } => format_titled_lines(
+            "削除されたスタンプ",
+            message
+                .sticker_items
+                .iter()
+                .filter(|sticker| !stickers_after.iter().any(|after| after.id == sticker.id))
+                .map(|sticker| format!("- {} `{}`", sticker.name, sticker.id)),
+        )?,
+        MessageLogKind::PollChanged { poll_after: Some(poll) } => format_poll("変更後の投票", poll)?,
+        MessageLogKind::PollChanged { poll_after: None } => MessageBuilder::new()
+            .push("### ")
+            .push_line(bold_underline("変更後の投票"))
+            .push("投票が削除されました。")
+            .build(),
+        MessageLogKind::ReactionsCleared { emoji: Some(emoji) } => MessageBuilder::new()
+            .push("### ")
+            .push_line(bold_underline("削除されたリアクション"))
+            .push_line(emoji.to_string().as_str())
+            .build(),
+        MessageLogKind::ReactionsCleared { emoji: None } => format_titled_lines(
+            "削除されたリアクション",
+            message
+                .reactions
+                .iter()
+                .map(|reaction| format!("- {}: {}件", reaction.reaction_type, reaction.count)),
+        )
+        .unwrap_or_else(|| {
+            MessageBuilder::new()
+                .push("### ")
+                .push_line(bold_underline("削除されたリアクション"))
+                .push("全てのリアクション")
+                .build()
+        }),
+        _ => return None,
+    };
+
+    Some(create_container_text(content))
 }
 
 fn format_omitted_equal(head: Option<&str>, tail: Option<&str>, count: usize) -> String {
@@ -257,23 +397,20 @@ pub(in crate::features::message_logging) fn build_linked_removed_attachment_comp
     result
 }
 
+/**
+実際にアップロードした添付ファイルのみを参照するコンポーネントを作成します。
+*/
 pub(in crate::features::message_logging) fn build_uploaded_removed_attachment_components<'a>(
-    message: &'a Message,
-    attachment_ids_after: &'a [AttachmentId],
+    uploaded_attachments: &'a [Attachment],
 ) -> Vec<CreateContainerComponent<'a>> {
-    if message.attachments.is_empty() {
-        return vec![];
-    }
-
-    let (galleries, files): (Vec<_>, Vec<_>) =
-        removed_attachments(message, attachment_ids_after).partition_map(|attachment| {
-            let item = CreateUnfurledMediaItem::new(format!("attachment://{}", attachment.filename));
-            if attachment.is_image() || attachment.is_video() {
-                Either::Left(item)
-            } else {
-                Either::Right(item)
-            }
-        });
+    let (galleries, files): (Vec<_>, Vec<_>) = uploaded_attachments.iter().partition_map(|attachment| {
+        let item = CreateUnfurledMediaItem::new(format!("attachment://{}", attachment.filename));
+        if attachment.is_image() || attachment.is_video() {
+            Either::Left(item)
+        } else {
+            Either::Right(item)
+        }
+    });
 
     let mut result = Vec::new();
     if !galleries.is_empty() {
@@ -309,6 +446,7 @@ pub(in crate::features::message_logging) fn build_uploaded_removed_attachment_co
 pub(in crate::features::message_logging) fn build_log_container_components<'a>(
     message: &Message,
     log_kind: &MessageLogKind,
+    referenced_message: Option<&Message>,
     basic_info_section_component: impl Into<Cow<'a, [CreateSectionComponent<'a>]>>,
     attachment_components: Vec<CreateContainerComponent<'a>>,
 ) -> Vec<CreateContainerComponent<'a>> {
@@ -326,9 +464,12 @@ pub(in crate::features::message_logging) fn build_log_container_components<'a>(
                         false,
                     ),
                 )),
-                build_message_reference_container_component(message),
+                build_message_reference_container_component(message, referenced_message),
                 build_poll_container_component(message),
-                build_diff_container_component(&message.content, log_kind.content_after()),
+                build_change_container_component(message, log_kind),
+                log_kind
+                    .content_after()
+                    .and_then(|content_after| build_diff_container_component(&message.content, content_after)),
             ]
             .into_iter()
             .filter_map(|c| c.map(|c| [create_separator(false), c].into_iter()))
@@ -341,3 +482,144 @@ pub(in crate::features::message_logging) fn build_log_container_components<'a>(
         )
         .collect_vec()
 }
+
+/**
+一括削除されたメッセージの送信者と件数を、件数の多い順に整形します。
+*/
+fn format_bulk_delete_authors(messages: &[Message]) -> String {
+    const MAX_AUTHORS: usize = 20;
+
+    let counts = messages
+        .iter()
+        .map(|message| message.author.id)
+        .counts()
+        .into_iter()
+        .sorted_by(|(_, a), (_, b)| b.cmp(a))
+        .collect_vec();
+
+    let mut lines = counts
+        .iter()
+        .take(MAX_AUTHORS)
+        .map(|(author_id, count)| format!("- <@{author_id}> `{author_id}`: {count}件"))
+        .collect_vec();
+
+    if counts.len() > MAX_AUTHORS {
+        lines.push(format!("- ... 他{}人", counts.len() - MAX_AUTHORS));
+    }
+
+    lines.join("\n")
+}
+
+pub(in crate::features::message_logging) fn build_bulk_delete_container_components<'a>(
+    channel_id: GenericChannelId,
+    deleted_count: usize,
+    messages: &[Message],
+    bot_count: usize,
+    failed_count: usize,
+    transcript_filename: Option<&str>,
+) -> Vec<CreateContainerComponent<'a>> {
+    let mut builder = MessageBuilder::new()
+        .push("### ")
+        .push_line(bold_underline("基本情報"))
+        .push_bold_safe("チャンネル: ")
+        .push_line(format!("<#{channel_id}>").as_str())
+        .push_bold_safe("削除数: ")
+        .push_line(format!("{deleted_count}件 (記録: {}件)", messages.len()).as_str());
+
+    if bot_count > 0 {
+        builder = builder
+            .push_bold_safe("Bot (記録対象外): ")
+            .push_line(format!("{bot_count}件").as_str());
+    }
+
+    if failed_count > 0 {
+        builder = builder
+            .push_bold_safe("記録失敗: ")
+            .push_line(format!("{failed_count}件").as_str());
+    }
+
+    if let (Some(first), Some(last)) = (messages.first(), messages.last()) {
+        builder = builder
+            .push_bold_safe("送信期間: ")
+            .push_short_date_medium_timestamp(first.timestamp)
+            .push_safe(" ~ ")
+            .push_short_date_medium_timestamp_line(last.timestamp);
+    }
+
+    builder = builder
+        .push_bold_safe("削除日時: ")
+        .push_short_date_medium_timestamp(Timestamp::now());
+
+    let mut result = vec![
+        create_container_text(format!("### **{}**", MessageLogKind::BulkDelete.title())),
+        create_separator(false),
+        create_container_text(builder.build()),
+    ];
+
+    if !messages.is_empty() {
+        result.extend([
+            create_separator(false),
+            create_container_text(
+                MessageBuilder::new()
+                    .push("### ")
+                    .push_line(bold_underline("送信者"))
+                    .push(format_bulk_delete_authors(messages).as_str())
+                    .build(),
+            ),
+        ]);
+    }
+
+    if let Some(filename) = transcript_filename {
+        result.extend([
+            create_separator(false),
+            create_container_text(
+                MessageBuilder::new()
+                    .push("### ")
+                    .push_line(bold_underline("削除されたメッセージ"))
+                    .build(),
+            ),
+            CreateContainerComponent::File(CreateFile::new(CreateUnfurledMediaItem::new(format!(
+                "attachment://{filename}"
+            )))),
+        ]);
+    }
+
+    result
+}
+
+/**
+一括削除されたメッセージの内容をテキストファイル用に整形します。
+
+添付ファイルはスナップショットから取得したものを `attachments` として受け取ります。
+ログに添付した添付ファイル (番号付き) は、無効になった URL の代わりにログの何番目の添付ファイルかを記載します。
+*/
+pub(in crate::features::message_logging) fn format_bulk_delete_transcript(
+    messages: &[(Message, Vec<(Attachment, Option<usize>)>)],
+) -> String {
+    messages
+        .iter()
+        .map(|(message, attachments)| {
+            let mut text = format!(
+                "[{}] {} ({}) - {}\n",
+                message.timestamp, message.author.name, message.author.id, message.id
+            );
+
+            if !message.content.is_empty() {
+                text.push_str(&message.content);
+                text.push('\n');
+            }
+
+            for (attachment, index) in attachments {
+                match index {
+                    Some(index) => text.push_str(&format!(
+                        "添付ファイル: {} (このログの{index}番目の添付ファイル)\n",
+                        attachment.filename
+                    )),
+                    None => text.push_str(&format!("添付ファイル: {} <{}>\n", attachment.filename, attachment.url)),
+                }
+            }
+
+            text
+        })
+        .join("\n")
+}
@@ -1,23 +1,36 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicBool, Ordering},
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
 };
 
+use itertools::Itertools;
 use serenity::{
     all::{Context, Message, MessageId},
     async_trait,
-    model::{event::FullEvent, id::GenericChannelId},
+    model::{
+        channel::{MessageFlags, ReactionType},
+        event::FullEvent,
+        id::{GenericChannelId, GuildId},
+    },
 };
-use tracing::error;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
 
 use crate::{
     app::{AppError, BotError},
     core::BotEventHandler,
     features::message_logging::{
-        log_sender::MessageLogSender, log_type::MessageLogKind, snapshot_store::MessageSnapshotStore,
+        log_sender::MessageLogSender,
+        log_type::MessageLogKind,
+        snapshot_store::{MessageSnapshotStore, SnapshotStoreExt},
     },
 };
 
+const SNAPSHOT_RETENTION_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
 fn has_removed_logged_attachments(message: &Message, new_message: &Message) -> bool {
     let attachment_ids_after: Vec<_> = new_message.attachments.iter().map(|attachment| attachment.id).collect();
 
@@ -34,19 +47,78 @@ fn message_update_log_content_changed(message: &Message, new_message: &Message)
     message.content != new_message.content || has_removed_logged_attachments(message, new_message)
 }
 
+fn has_suppressed_embeds(message: &Message) -> bool {
+    message
+        .flags
+        .is_some_and(|flags| flags.contains(MessageFlags::SUPPRESS_EMBEDS))
+}
+
+fn is_poll_finalized(message: &Message) -> bool {
+    message
+        .poll
+        .as_ref()
+        .and_then(|poll| poll.results.as_ref())
+        .is_some_and(|results| results.is_finalized)
+}
+
+/**
+ * メッセージの内容以外の変化から、ログの種類を列挙する
+ */
+fn message_update_optional_log_kinds<'a>(message: &Message, new_message: &'a Message) -> Vec<MessageLogKind<'a>> {
+    let mut kinds = Vec::new();
+
+    if !message.pinned() && new_message.pinned() {
+        kinds.push(MessageLogKind::Pin);
+    } else if message.pinned() && !new_message.pinned() {
+        kinds.push(MessageLogKind::Unpin);
+    }
+
+    if !has_suppressed_embeds(message) && has_suppressed_embeds(new_message) {
+        if !message.embeds.is_empty() {
+            kinds.push(MessageLogKind::EmbedsSuppressed);
+        }
+    } else if message.embeds.iter().any(|embed| {
+        !new_message
+            .embeds
+            .iter()
+            .any(|after| after.url == embed.url && after.title == embed.title)
+    }) {
+        kinds.push(MessageLogKind::EmbedRemoved {
+            embeds_after: &new_message.embeds,
+        });
+    }
+
+    if message
+        .sticker_items
+        .iter()
+        .any(|sticker| !new_message.sticker_items.iter().any(|after| after.id == sticker.id))
+    {
+        kinds.push(MessageLogKind::StickerRemoved {
+            stickers_after: &new_message.sticker_items,
+        });
+    }
+
+    if message.poll.is_some()
+        && (new_message.poll.is_none() || is_poll_finalized(message) != is_poll_finalized(new_message))
+    {
+        kinds.push(MessageLogKind::PollChanged {
+            poll_after: new_message.poll.as_deref(),
+        });
+    }
+
+    kinds
+}
+
 pub struct MessageLoggingEventHandler {
     rebuilt_snapshot_store: AtomicBool,
-    snapshot_store: Arc<MessageSnapshotStore>,
     log_sender: MessageLogSender,
 }
 
 impl MessageLoggingEventHandler {
     pub fn new() -> Self {
-        let snapshot_store = Arc::new(MessageSnapshotStore::new());
         Self {
             rebuilt_snapshot_store: AtomicBool::new(false),
-            log_sender: MessageLogSender::new(Arc::clone(&snapshot_store)),
-            snapshot_store,
+            log_sender: MessageLogSender::new(),
         }
     }
 
@@ -55,13 +127,42 @@ impl MessageLoggingEventHandler {
             return;
         }
 
+        let snapshot_store = match ctx.snapshot_store() {
+            Ok(snapshot_store) => snapshot_store,
+            Err(error) => {
+                error!("Failed to start attachment snapshot maintenance: {error:#}");
+                return;
+            }
+        };
         let ctx = ctx.clone();
-        let snapshot_store = self.snapshot_store.clone();
         tokio::spawn(async move {
-            snapshot_store.rebuild(ctx).await;
+            snapshot_store.rebuild(ctx.clone()).await;
+            Self::run_snapshot_prune_loop(ctx, snapshot_store).await;
         });
     }
 
+    async fn run_snapshot_prune_loop(ctx: Context, snapshot_store: Arc<MessageSnapshotStore>) {
+        loop {
+            let config = ctx.app_config().await;
+            let Some(retention) = &config.message_logging.snapshot_retention else {
+                // 保持設定が無い場合も、設定の再読み込みに備えて定期的に確認する
+                sleep(SNAPSHOT_RETENTION_CHECK_INTERVAL).await;
+                continue;
+            };
+
+            match snapshot_store
+                .prune(&ctx, retention.max_age, retention.max_count_per_channel)
+                .await
+            {
+                Ok(0) => {}
+                Ok(count) => info!("Pruned {count} expired message attachment snapshots"),
+                Err(error) => error!("Failed to prune message attachment snapshots: {error:#}"),
+            }
+
+            sleep(retention.prune_interval).await;
+        }
+    }
+
     async fn handle_message_update(
         &self,
         ctx: &Context,
@@ -73,24 +174,64 @@ impl MessageLoggingEventHandler {
             id: new_message.id.to_string(),
         })?;
 
-        if !message_update_log_content_changed(message, new_message) {
+        if message_update_log_content_changed(message, new_message) {
+            self.log_sender
+                .send(
+                    ctx,
+                    message,
+                    MessageLogKind::Edit {
+                        content_after: &new_message.content,
+                        attachments_after: &new_message.attachments,
+                    },
+                )
+                .await?;
+
+            ctx.snapshot_store()?.sync(ctx, Some(message), new_message).await?;
+        }
+
+        for log_kind in message_update_optional_log_kinds(message, new_message) {
+            self.send_optional_log(ctx, message, log_kind).await?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * `optional_kinds` で有効化されている場合のみログを送信する
+     */
+    async fn send_optional_log(
+        &self,
+        ctx: &Context,
+        message: &Message,
+        log_kind: MessageLogKind<'_>,
+    ) -> Result<(), AppError> {
+        let config = ctx.app_config().await;
+        if !config.message_logging.optional_kinds.contains(&log_kind.route_kind()) {
             return Ok(());
         }
 
-        self.log_sender
-            .send(
-                ctx,
-                message,
-                MessageLogKind::Edit {
-                    content_after: &new_message.content,
-                    attachments_after: &new_message.attachments,
-                },
-            )
-            .await?;
+        self.log_sender.send(ctx, message, log_kind).await
+    }
+
+    async fn handle_reactions_cleared(
+        &self,
+        ctx: &Context,
+        channel_id: GenericChannelId,
+        message_id: MessageId,
+        emoji: Option<&ReactionType>,
+    ) -> Result<(), AppError> {
+        let log_kind = MessageLogKind::ReactionsCleared { emoji };
+        let config = ctx.app_config().await;
+        if !config.message_logging.optional_kinds.contains(&log_kind.route_kind()) {
+            return Ok(());
+        }
 
-        self.snapshot_store.sync(ctx, new_message).await?;
+        // キャッシュに無いメッセージは内容を残せないため、エラーにせず無視する
+        let Some(message) = ctx.cache.message(channel_id, message_id).map(|message| message.clone()) else {
+            return Ok(());
+        };
 
-        Ok(())
+        self.log_sender.send(ctx, &message, log_kind).await
     }
 
     async fn handle_message_delete(
@@ -110,7 +251,7 @@ impl MessageLoggingEventHandler {
 
         self.log_sender.send(ctx, &message, MessageLogKind::Delete).await?;
 
-        self.snapshot_store
+        ctx.snapshot_store()?
             .delete(ctx, *channel_id, *deleted_message_id)
             .await?;
 
@@ -121,26 +262,44 @@ impl MessageLoggingEventHandler {
         &self,
         ctx: &Context,
         channel_id: &GenericChannelId,
+        guild_id: &Option<GuildId>,
         deleted_message_ids: &[MessageId],
     ) -> Result<(), AppError> {
-        for message_id in deleted_message_ids {
-            let message = match ctx.cache.message(*channel_id, *message_id) {
-                Some(message) => message.clone(),
-                None => {
-                    error!("Failed to get message: {message_id}");
-                    continue;
-                }
-            };
+        let messages = deleted_message_ids
+            .iter()
+            .filter_map(|message_id| {
+                ctx.cache
+                    .message(*channel_id, *message_id)
+                    .map(|message| message.clone())
+            })
+            .collect_vec();
 
-            self.log_sender.send(ctx, &message, MessageLogKind::Delete).await?;
-            self.snapshot_store.delete(ctx, *channel_id, *message_id).await?;
+        if messages.len() < deleted_message_ids.len() {
+            warn!(
+                "Failed to get {} of {} bulk deleted messages from cache",
+                deleted_message_ids.len() - messages.len(),
+                deleted_message_ids.len()
+            );
         }
 
-        Ok(())
+        let log_result = self
+            .log_sender
+            .send_bulk_delete(ctx, *channel_id, *guild_id, deleted_message_ids.len(), messages)
+            .await;
+
+        // ログの送信に失敗した場合でもスナップショットは削除する
+        let snapshot_store = ctx.snapshot_store()?;
+        for message_id in deleted_message_ids {
+            if let Err(error) = snapshot_store.delete(ctx, *channel_id, *message_id).await {
+                error!("Failed to delete attachment snapshot for message {message_id}: {error:#}");
+            }
+        }
+
+        log_result
     }
 
     async fn handle_message_create(&self, ctx: &Context, new_message: &Message) -> Result<(), AppError> {
-        self.snapshot_store.sync(ctx, new_message).await?;
+        ctx.snapshot_store()?.sync(ctx, None, new_message).await?;
 
         Ok(())
     }
@@ -167,11 +326,34 @@ impl BotEventHandler for MessageLoggingEventHandler {
             FullEvent::MessageDeleteBulk {
                 channel_id,
                 multiple_deleted_messages_ids: messages_ids,
+                guild_id,
                 ..
-            } => self.handle_message_delete_bulk(ctx, channel_id, messages_ids).await?,
+            } => {
+                self.handle_message_delete_bulk(ctx, channel_id, guild_id, messages_ids)
+                    .await?
+            }
 
             FullEvent::Message { new_message, .. } => self.handle_message_create(ctx, new_message).await?,
 
+            FullEvent::ReactionRemoveAll {
+                channel_id,
+                removed_from_message_id,
+                ..
+            } => {
+                self.handle_reactions_cleared(ctx, *channel_id, *removed_from_message_id, None)
+                    .await?
+            }
+
+            FullEvent::ReactionRemoveEmoji { removed_reactions, .. } => {
+                self.handle_reactions_cleared(
+                    ctx,
+                    removed_reactions.channel_id,
+                    removed_reactions.message_id,
+                    Some(&removed_reactions.emoji),
+                )
+                .await?
+            }
+
             _ => {}
         }
 
@@ -1,35 +1,47 @@
-use std::sync::Arc;
+use std::collections::HashMap;
 
 use anyhow::Context as _;
+use itertools::Itertools;
 use serenity::{
-    all::{Context, Mentionable, Message, MessageBuilder, Timestamp},
-    builder::{CreateContainerComponent, CreateSectionComponent, EditAttachments, EditMessage},
+    all::{Context, Mentionable, Message, MessageBuilder, MessageReferenceKind, Timestamp},
+    builder::{CreateAttachment, CreateContainerComponent, CreateSectionComponent},
+    model::id::{GenericChannelId, GuildId},
 };
+use tracing::error;
 
 use crate::{
     app::{
         AppError, BotDataExt,
-        utils::components::{create_container, create_section_text},
+        config::MessageLogRouteKind,
+        utils::{
+            components::{create_container, create_section_text},
+            log_message::{LogMessage, edit_log_message, send_log_message},
+        },
     },
     extensions::MessageBuilderTimestampExt,
     features::message_logging::{
         component_builder::{
-            bold_underline, build_linked_removed_attachment_components, build_log_container_components,
-            build_uploaded_removed_attachment_components,
+            bold_underline, build_bulk_delete_container_components, build_linked_removed_attachment_components,
+            build_log_container_components, build_uploaded_removed_attachment_components,
+            format_bulk_delete_transcript,
         },
         log_type::MessageLogKind,
-        snapshot_store::MessageSnapshotStore,
+        snapshot_store::SnapshotStoreExt,
     },
-    utils::{create_components_v2_message, create_safe_allowed_mentions, send_message},
+    utils::channel_ancestor_ids,
 };
 
-pub struct MessageLogSender {
-    snapshot_store: Arc<MessageSnapshotStore>,
-}
+/// 一括削除のログに添付する、ローカルに保存された添付ファイルの最大数 (書き起こしと合わせて10件まで)
+const MAX_BULK_DELETE_ATTACHMENTS: usize = 9;
+
+/// 一括削除のログに添付する、ローカルに保存された添付ファイルの合計の最大サイズ (バイト)
+const MAX_BULK_DELETE_ATTACHMENT_SIZE: u32 = 8 * 1024 * 1024;
+
+pub struct MessageLogSender;
 
 impl MessageLogSender {
-    pub fn new(snapshot_store: Arc<MessageSnapshotStore>) -> Self {
-        Self { snapshot_store }
+    pub fn new() -> Self {
+        Self
     }
 
     pub async fn send<'a>(
@@ -42,21 +54,144 @@ impl MessageLogSender {
             return Ok(());
         }
 
-        let attachment_ids_after = log_kind.attachment_ids_after();
+        let attachment_ids_after = log_kind.attachment_ids_after(message);
+        let referenced_message = find_referenced_message(ctx, message);
         let message_basic_info = build_message_basic_info(message, &log_kind);
         let log_container_components = build_log_container_components(
             message,
             &log_kind,
+            referenced_message.as_ref(),
             &message_basic_info,
             build_linked_removed_attachment_components(message, &attachment_ids_after),
         );
 
         let mut log_message = self
-            .send_initial_log_message(ctx, &log_kind, log_container_components)
+            .send_initial_log_message(ctx, message, &log_kind, log_container_components)
             .await?;
 
-        self.upload_removed_attachments(ctx, &mut log_message, message, &log_kind, &message_basic_info)
-            .await?;
+        self.upload_removed_attachments(
+            ctx,
+            &mut log_message,
+            message,
+            &log_kind,
+            referenced_message.as_ref(),
+            &message_basic_info,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /**
+    一括削除されたメッセージを 1 件のログにまとめて送信します。
+
+    キャッシュに無いメッセージや、個々のメッセージの添付ファイル取得に失敗しても中断せず、失敗件数としてログに記載します。
+    */
+    pub async fn send_bulk_delete(
+        &self,
+        ctx: &Context,
+        channel_id: GenericChannelId,
+        guild_id: Option<GuildId>,
+        deleted_count: usize,
+        messages: Vec<Message>,
+    ) -> Result<(), AppError> {
+        let cached_count = messages.len();
+        let messages = messages
+            .into_iter()
+            .filter(|message| !message.author.bot())
+            .sorted_by_key(|message| message.id)
+            .collect_vec();
+        // Bot のメッセージは記録の対象外のため、件数のみ表示する
+        let bot_count = cached_count - messages.len();
+
+        // キャッシュに無く記録できなかったメッセージも失敗件数に含める
+        let mut failed_count = deleted_count.saturating_sub(cached_count);
+        let mut transcript_entries = Vec::with_capacity(messages.len());
+        let mut archived_files = Vec::new();
+        let mut archived_size = 0;
+        let snapshot_store = ctx.snapshot_store()?;
+        for message in &messages {
+            let attachments = match snapshot_store.attachments_for(ctx, message).await {
+                Ok(attachments) => attachments.to_vec(),
+                Err(error) => {
+                    error!(
+                        "Failed to get attachment snapshot for message {}: {error:#}",
+                        message.id
+                    );
+                    failed_count += 1;
+                    message.attachments.to_vec()
+                }
+            };
+
+            // 元の添付ファイルの URL は削除後に無効になるため、ローカルに保存されたものはログに添付する
+            let mut archived = if attachments.is_empty() {
+                HashMap::new()
+            } else {
+                snapshot_store
+                    .archived_attachments(ctx, message)
+                    .await
+                    .unwrap_or_else(|error| {
+                        error!(
+                            "Failed to load archived attachments for message {}: {error:#}",
+                            message.id
+                        );
+                        HashMap::new()
+                    })
+            };
+            let attachments = attachments
+                .into_iter()
+                .map(|attachment| {
+                    let file = archived.remove(&attachment.id).filter(|_| {
+                        archived_files.len() < MAX_BULK_DELETE_ATTACHMENTS
+                            && archived_size + attachment.size <= MAX_BULK_DELETE_ATTACHMENT_SIZE
+                    });
+                    // 1番目の添付ファイルは書き起こし
+                    let index = file.map(|file| {
+                        archived_size += attachment.size;
+                        archived_files.push(file);
+                        archived_files.len() + 1
+                    });
+                    (attachment, index)
+                })
+                .collect_vec();
+            transcript_entries.push((message.clone(), attachments));
+        }
+
+        let transcript_filename = format!("deleted_messages_{channel_id}.txt");
+        let has_transcript = !transcript_entries.is_empty();
+
+        let log_kind = MessageLogKind::BulkDelete;
+        let config = ctx.app_config().await;
+        let destination = config
+            .message_logging
+            .log_destination(&channel_ancestor_ids(ctx, guild_id, channel_id), log_kind.route_kind());
+
+        let mut log_message = LogMessage::components_v2(vec![create_container(
+            build_bulk_delete_container_components(
+                channel_id,
+                deleted_count,
+                &messages,
+                bot_count,
+                failed_count,
+                has_transcript.then_some(transcript_filename.as_str()),
+            ),
+            Some(log_kind.color()),
+            false,
+        )]);
+
+        if has_transcript {
+            log_message = log_message.add_file(CreateAttachment::bytes(
+                format_bulk_delete_transcript(&transcript_entries).into_bytes(),
+                transcript_filename.clone(),
+            ));
+        }
+        for file in archived_files {
+            log_message = log_message.add_file(file);
+        }
+
+        send_log_message(ctx, destination, log_message)
+            .await
+            .context("Failed to send bulk delete log")?;
 
         Ok(())
     }
@@ -64,13 +199,20 @@ impl MessageLogSender {
     async fn send_initial_log_message<'a>(
         &self,
         ctx: &Context,
+        message: &Message,
         log_kind: &MessageLogKind<'a>,
         log_container_components: Vec<CreateContainerComponent<'a>>,
     ) -> Result<Message, AppError> {
-        send_message(
+        let config = ctx.app_config().await;
+        let destination = config.message_logging.log_destination(
+            &channel_ancestor_ids(ctx, message.guild_id, message.channel_id),
+            log_kind.route_kind(),
+        );
+
+        send_log_message(
             ctx,
-            &ctx.app_config().await.message_logging.channel_id,
-            create_components_v2_message(vec![create_container(
+            destination,
+            LogMessage::components_v2(vec![create_container(
                 log_container_components,
                 Some(log_kind.color()),
                 false,
@@ -86,46 +228,75 @@ impl MessageLogSender {
         log_message: &mut Message,
         message: &'a Message,
         log_kind: &MessageLogKind<'a>,
+        referenced_message: Option<&Message>,
         message_basic_info: &'a [CreateSectionComponent<'a>],
     ) -> Result<(), AppError> {
-        if message.attachments.is_empty() {
+        let attachment_ids_after = log_kind.attachment_ids_after(message);
+        if message
+            .attachments
+            .iter()
+            .all(|attachment| attachment_ids_after.contains(&attachment.id))
+        {
             return Ok(());
         }
 
-        let attachment_ids_after = log_kind.attachment_ids_after();
-        let attachments = self
-            .snapshot_store
+        let (uploaded_attachments, attachments): (Vec<_>, Vec<_>) = ctx
+            .snapshot_store()?
             .upload_attachments(ctx, message, &attachment_ids_after)
-            .await?;
-
-        let mut edit_attachments = EditAttachments::new();
-        for attachment in attachments {
-            edit_attachments = edit_attachments.add(attachment);
+            .await?
+            .into_iter()
+            .unzip();
+        if uploaded_attachments.is_empty() {
+            return Ok(());
         }
 
-        log_message
-            .edit(
-                &ctx,
-                EditMessage::new()
-                    .allowed_mentions(create_safe_allowed_mentions())
-                    .components(vec![create_container(
-                        build_log_container_components(
-                            message,
-                            log_kind,
-                            message_basic_info,
-                            build_uploaded_removed_attachment_components(message, &attachment_ids_after),
-                        ),
-                        Some(log_kind.color()),
-                        false,
-                    )])
-                    .attachments(edit_attachments),
-            )
-            .await?;
+        let config = ctx.app_config().await;
+        let destination = config.message_logging.log_destination(
+            &channel_ancestor_ids(ctx, message.guild_id, message.channel_id),
+            log_kind.route_kind(),
+        );
+
+        edit_log_message(
+            ctx,
+            destination,
+            log_message,
+            vec![create_container(
+                build_log_container_components(
+                    message,
+                    log_kind,
+                    referenced_message,
+                    message_basic_info,
+                    build_uploaded_removed_attachment_components(&uploaded_attachments),
+                ),
+                Some(log_kind.color()),
+                false,
+            )],
+            attachments,
+        )
+        .await?;
 
         Ok(())
     }
 }
 
+/**
+返信先のメッセージを、メッセージに含まれるものかキャッシュから取得します。
+*/
+fn find_referenced_message(ctx: &Context, message: &Message) -> Option<Message> {
+    if let Some(referenced_message) = &message.referenced_message {
+        return Some((**referenced_message).clone());
+    }
+
+    let message_reference = message.message_reference.as_ref()?;
+    if message_reference.kind != MessageReferenceKind::Default {
+        return None;
+    }
+
+    ctx.cache
+        .message(message_reference.channel_id, message_reference.message_id?)
+        .map(|message| message.clone())
+}
+
 fn build_message_basic_info<'a>(message: &Message, log_kind: &MessageLogKind) -> [CreateSectionComponent<'a>; 1] {
     [create_section_text(
         MessageBuilder::new()
@@ -1,15 +1,38 @@
 use itertools::Itertools;
 use serenity::{
-    model::{Color, channel::Attachment, id::AttachmentId},
+    model::{
+        Color,
+        channel::{Attachment, Embed, Message, Poll, ReactionType},
+        id::AttachmentId,
+        sticker::StickerItem,
+    },
     small_fixed_array::{FixedArray, FixedString},
 };
 
+use crate::app::config::MessageLogRouteKind;
+
 pub(in crate::features::message_logging) enum MessageLogKind<'a> {
     Edit {
         content_after: &'a FixedString<u16>,
         attachments_after: &'a FixedArray<Attachment>,
     },
     Delete,
+    BulkDelete,
+    EmbedRemoved {
+        embeds_after: &'a FixedArray<Embed>,
+    },
+    EmbedsSuppressed,
+    StickerRemoved {
+        stickers_after: &'a FixedArray<StickerItem>,
+    },
+    PollChanged {
+        poll_after: Option<&'a Poll>,
+    },
+    Pin,
+    Unpin,
+    ReactionsCleared {
+        emoji: Option<&'a ReactionType>,
+    },
 }
 
 impl MessageLogKind<'_> {
@@ -17,6 +40,28 @@ impl MessageLogKind<'_> {
         match self {
             MessageLogKind::Edit { .. } => "編集",
             MessageLogKind::Delete => "削除",
+            MessageLogKind::BulkDelete => "一括削除",
+            MessageLogKind::EmbedRemoved { .. } => "埋め込み削除",
+            MessageLogKind::EmbedsSuppressed => "埋め込み非表示",
+            MessageLogKind::StickerRemoved { .. } => "スタンプ削除",
+            MessageLogKind::PollChanged { .. } => "投票変更",
+            MessageLogKind::Pin => "ピン留め",
+            MessageLogKind::Unpin => "ピン留め解除",
+            MessageLogKind::ReactionsCleared { .. } => "リアクション削除",
+        }
+    }
+
+    pub fn route_kind(&self) -> MessageLogRouteKind {
+        match self {
+            MessageLogKind::Edit { .. } => MessageLogRouteKind::Edit,
+            MessageLogKind::Delete => MessageLogRouteKind::Delete,
+            MessageLogKind::BulkDelete => MessageLogRouteKind::BulkDelete,
+            MessageLogKind::EmbedRemoved { .. } => MessageLogRouteKind::EmbedRemoved,
+            MessageLogKind::EmbedsSuppressed => MessageLogRouteKind::EmbedsSuppressed,
+            MessageLogKind::StickerRemoved { .. } => MessageLogRouteKind::StickerRemoved,
+            MessageLogKind::PollChanged { .. } => MessageLogRouteKind::PollChanged,
+            MessageLogKind::Pin | MessageLogKind::Unpin => MessageLogRouteKind::Pin,
+            MessageLogKind::ReactionsCleared { .. } => MessageLogRouteKind::ReactionsCleared,
         }
     }
 
@@ -27,21 +72,34 @@ impl MessageLogKind<'_> {
     pub fn color(&self) -> Color {
         match self {
             MessageLogKind::Edit { .. } => Color::ORANGE,
-            MessageLogKind::Delete => Color::RED,
+            MessageLogKind::Delete | MessageLogKind::BulkDelete => Color::RED,
+            MessageLogKind::EmbedRemoved { .. } => Color::DARK_ORANGE,
+            MessageLogKind::EmbedsSuppressed => Color::GOLD,
+            MessageLogKind::StickerRemoved { .. } => Color::MAGENTA,
+            MessageLogKind::PollChanged { .. } => Color::BLUE,
+            MessageLogKind::Pin => Color::TEAL,
+            MessageLogKind::Unpin => Color::DARK_TEAL,
+            MessageLogKind::ReactionsCleared { .. } => Color::PURPLE,
         }
     }
 
-    pub fn content_after(&self) -> &str {
+    /**
+    テキスト差分を表示する場合の変更後の内容
+    */
+    pub fn content_after(&self) -> Option<&str> {
         match self {
-            MessageLogKind::Edit { content_after, .. } => content_after,
-            MessageLogKind::Delete => Default::default(),
+            MessageLogKind::Edit { content_after, .. } => Some(content_after),
+            MessageLogKind::Delete | MessageLogKind::BulkDelete => Some(Default::default()),
+            _ => None,
         }
     }
 
-    pub fn attachment_ids_after(&self) -> Vec<AttachmentId> {
+    pub fn attachment_ids_after(&self, message: &Message) -> Vec<AttachmentId> {
         match self {
             MessageLogKind::Edit { attachments_after, .. } => attachments_after.iter().map(|a| a.id).collect_vec(),
-            MessageLogKind::Delete => Default::default(),
+            MessageLogKind::Delete | MessageLogKind::BulkDelete => Default::default(),
+            // 添付ファイルは変化していない
+            _ => message.attachments.iter().map(|a| a.id).collect_vec(),
         }
     }
 }
@@ -1,7 +1,11 @@
+mod attachment_archive;
+mod command;
 mod component_builder;
 mod handler;
 mod log_sender;
 mod log_type;
 mod snapshot_store;
 
+pub use command::snapshot;
 pub use handler::MessageLoggingEventHandler;
+pub(in crate::features) use snapshot_store::MessageSnapshotStore;
@@ -1,29 +1,91 @@
-use dashmap::{DashMap, mapref::entry::Entry};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+use chrono::{Duration, Utc};
+use dashmap::DashMap;
 use futures::StreamExt;
 use serenity::{
+    Error as SerenityError,
     all::prelude::{CacheHttp, Context},
     builder::CreateAttachment,
+    http::HttpError,
     model::{
+        Timestamp,
         channel::{Attachment, Message, MessageReference, MessageReferenceKind},
         id::{AttachmentId, GenericChannelId, MessageId, UserId},
     },
     small_fixed_array::FixedArray,
 };
-use tracing::{error, info};
+use sqlx::PgPool;
+use tracing::{error, info, warn};
 
 use crate::{
-    app::{AppError, BotDataExt},
+    app::{AppError, BotDataExt, BotError, config::SnapshotBackend},
+    features::message_logging::attachment_archive::LocalAttachmentArchive,
     utils::create_safe_message,
 };
 
-pub(in crate::features::message_logging) struct MessageSnapshotStore {
-    snapshot_ids: DashMap<GenericChannelId, DashMap<MessageId, MessageId>>,
+/// Discord のエポック (2015-01-01T00:00:00Z) のミリ秒
+const DISCORD_EPOCH_MILLIS: i64 = 1_420_070_400_000;
+
+/**
+指定された日時以前に作成されたメッセージのIDの上限を求めます。
+*/
+pub(in crate::features::message_logging) fn snowflake_upper_bound(timestamp: chrono::DateTime<Utc>) -> i64 {
+    (timestamp.timestamp_millis() - DISCORD_EPOCH_MILLIS).max(0) << 22
+}
+
+fn is_not_found(error: &SerenityError) -> bool {
+    matches!(
+        error,
+        SerenityError::Http(HttpError::UnsuccessfulRequest(response)) if response.status_code.as_u16() == 404
+    )
+}
+
+pub(in crate::features) struct SnapshotStats {
+    pub total_count: i64,
+    pub channel_count: i64,
+    pub oldest_snapshot_message_id: Option<MessageId>,
+    pub largest_channels: Vec<(GenericChannelId, i64)>,
+}
+
+/**
+元のメッセージと、添付ファイルを転送したスナップショットメッセージの対応を管理します。
+
+対応はデータベースに保存し、メッセージの削除・編集ごとの参照はメモリ上のインデックスから行います。
+*/
+pub(in crate::features) struct MessageSnapshotStore {
+    archive: LocalAttachmentArchive,
+    index: DashMap<(GenericChannelId, MessageId), MessageId>,
+    /// データベースの全ての対応をインデックスに読み込んだかどうか (読み込み前はデータベースを参照する)
+    loaded: AtomicBool,
+}
+
+/**
+`BotData` に登録された `MessageSnapshotStore` を取得します。
+*/
+pub(in crate::features::message_logging) trait SnapshotStoreExt {
+    fn snapshot_store(&self) -> Result<Arc<MessageSnapshotStore>, BotError>;
+}
+
+impl<T: BotDataExt> SnapshotStoreExt for T {
+    fn snapshot_store(&self) -> Result<Arc<MessageSnapshotStore>, BotError> {
+        self.extension()
+    }
 }
 
 impl MessageSnapshotStore {
     pub fn new() -> Self {
         Self {
-            snapshot_ids: DashMap::default(),
+            archive: LocalAttachmentArchive::new(),
+            index: DashMap::new(),
+            loaded: AtomicBool::new(false),
         }
     }
 
@@ -33,25 +95,38 @@ impl MessageSnapshotStore {
         channel_id: GenericChannelId,
         message_id: MessageId,
     ) -> Result<(), AppError> {
-        let Some(entry) = self.snapshot_ids.get(&channel_id) else {
+        // 保存方式が切り替えられている可能性があるため、両方から削除する
+        if let Some(local_archive) = &ctx.app_config().await.message_logging.local_archive {
+            self.archive.delete(ctx, local_archive, channel_id, message_id).await?;
+        }
+
+        let database = ctx.database();
+        let Some(snapshot_message_id) = self.find(&database, channel_id, message_id).await? else {
             return Ok(());
         };
 
-        if let Some((_, snapshot_message_id)) = entry.remove(&message_id) {
-            ctx.app_config()
-                .await
-                .message_logging
-                .snapshot_channel_id
-                .delete_message(ctx.http(), snapshot_message_id, None)
-                .await?;
+        // 削除に失敗した場合に再試行できるよう、対応はメッセージを削除できてから削除する
+        if let Err(error) = ctx
+            .app_config()
+            .await
+            .message_logging
+            .snapshot_channel_id
+            .delete_message(ctx.http(), snapshot_message_id, None)
+            .await
+            && !is_not_found(&error)
+        {
+            return Err(error.into());
         }
 
+        self.delete_persisted(&database, channel_id, message_id).await?;
+
         Ok(())
     }
 
     pub async fn update(&self, ctx: &Context, message: &Message) -> Result<(), AppError> {
         let config = &ctx.app_config().await.message_logging;
         let snapshot_channel_id = config.snapshot_channel_id;
+        let database = ctx.database();
 
         let snapshot_message = snapshot_channel_id
             .send_message(
@@ -62,33 +137,181 @@ impl MessageSnapshotStore {
             )
             .await?;
 
-        let old = {
-            let entry = self.snapshot_ids.entry(message.channel_id).or_default();
-            entry.insert(message.id, snapshot_message.id)
-        };
+        let old = self.find(&database, message.channel_id, message.id).await?;
+
+        // 古いスナップショットの削除に失敗しても新しいものを参照できるよう、対応を先に保存する
+        self.persist(&database, message.channel_id, message.id, snapshot_message.id)
+            .await?;
 
-        if let Some(old) = old {
-            snapshot_channel_id.delete_message(ctx.http(), old, None).await?;
+        if let Some(old) = old
+            && let Err(error) = snapshot_channel_id.delete_message(ctx.http(), old, None).await
+            && !is_not_found(&error)
+        {
+            return Err(error.into());
         }
 
         Ok(())
     }
 
-    pub async fn sync(&self, ctx: &Context, message: &Message) -> Result<(), AppError> {
+    /**
+    メッセージの添付ファイルのスナップショットを作成・更新します。
+
+    添付ファイルが無くなった場合は、編集前のメッセージに添付ファイルがあったときのみスナップショットを削除します。
+    新しいメッセージの場合は `old` に `None` を渡します。
+    */
+    pub async fn sync(&self, ctx: &Context, old: Option<&Message>, message: &Message) -> Result<(), AppError> {
         if message.author.bot() {
             return Ok(());
         }
 
         if message.attachments.is_empty() {
-            self.delete(ctx, message.channel_id, message.id).await
-        } else {
-            self.update(ctx, message).await
+            if old.is_some_and(|old| !old.attachments.is_empty()) {
+                return self.delete(ctx, message.channel_id, message.id).await;
+            }
+            return Ok(());
+        }
+
+        let config = ctx.app_config().await;
+        match (
+            config.message_logging.snapshot_backend(message.guild_id),
+            &config.message_logging.local_archive,
+        ) {
+            (SnapshotBackend::Local, Some(local_archive)) => self.archive.store(ctx, local_archive, message).await,
+            _ => self.update(ctx, message).await,
+        }
+    }
+
+    async fn find(
+        &self,
+        database: &PgPool,
+        channel_id: GenericChannelId,
+        message_id: MessageId,
+    ) -> Result<Option<MessageId>, AppError> {
+        if let Some(snapshot_message_id) = self.index.get(&(channel_id, message_id)) {
+            return Ok(Some(*snapshot_message_id));
+        }
+        if self.loaded.load(Ordering::Acquire) {
+            return Ok(None);
         }
+
+        let row: Option<(i64,)> = sqlx::query_as(
+            "SELECT snapshot_message_id FROM message_snapshots WHERE channel_id = $1 AND message_id = $2",
+        )
+        .bind(channel_id.get() as i64)
+        .bind(message_id.get() as i64)
+        .fetch_optional(database)
+        .await?;
+
+        Ok(row.map(|(id,)| MessageId::new(id as u64)))
     }
 
+    async fn persist(
+        &self,
+        database: &PgPool,
+        channel_id: GenericChannelId,
+        message_id: MessageId,
+        snapshot_message_id: MessageId,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO message_snapshots (channel_id, message_id, snapshot_message_id) VALUES ($1, $2, $3)
+            ON CONFLICT (channel_id, message_id) DO UPDATE SET snapshot_message_id = EXCLUDED.snapshot_message_id",
+        )
+        .bind(channel_id.get() as i64)
+        .bind(message_id.get() as i64)
+        .bind(snapshot_message_id.get() as i64)
+        .execute(database)
+        .await?;
+
+        self.index.insert((channel_id, message_id), snapshot_message_id);
+        Ok(())
+    }
+
+    /**
+    対応が未登録の場合のみ保存し、保存したかどうかを返します。
+    */
+    async fn persist_if_absent(
+        &self,
+        database: &PgPool,
+        channel_id: GenericChannelId,
+        message_id: MessageId,
+        snapshot_message_id: MessageId,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "INSERT INTO message_snapshots (channel_id, message_id, snapshot_message_id) VALUES ($1, $2, $3)
+            ON CONFLICT (channel_id, message_id) DO NOTHING",
+        )
+        .bind(channel_id.get() as i64)
+        .bind(message_id.get() as i64)
+        .bind(snapshot_message_id.get() as i64)
+        .execute(database)
+        .await?;
+
+        let persisted = result.rows_affected() > 0;
+        if persisted {
+            self.index.insert((channel_id, message_id), snapshot_message_id);
+        }
+        Ok(persisted)
+    }
+
+    async fn delete_persisted(
+        &self,
+        database: &PgPool,
+        channel_id: GenericChannelId,
+        message_id: MessageId,
+    ) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM message_snapshots WHERE channel_id = $1 AND message_id = $2")
+            .bind(channel_id.get() as i64)
+            .bind(message_id.get() as i64)
+            .execute(database)
+            .await?;
+
+        self.index.remove(&(channel_id, message_id));
+        Ok(())
+    }
+
+    /**
+    データベースの全ての対応をインデックスに読み込み、読み込んだ件数と最後に記録されたスナップショットメッセージのIDを返します。
+    */
+    async fn load_index(&self, database: &PgPool) -> Result<(usize, Option<MessageId>), AppError> {
+        let rows: Vec<(i64, i64, i64)> =
+            sqlx::query_as("SELECT channel_id, message_id, snapshot_message_id FROM message_snapshots")
+                .fetch_all(database)
+                .await?;
+
+        let mut last_snapshot_message_id = None;
+        for &(channel_id, message_id, snapshot_message_id) in &rows {
+            let snapshot_message_id = MessageId::new(snapshot_message_id as u64);
+            last_snapshot_message_id = last_snapshot_message_id.max(Some(snapshot_message_id));
+            // 読み込み中に記録されたものを上書きしないよう、未登録の場合のみ追加する
+            self.index
+                .entry((
+                    GenericChannelId::new(channel_id as u64),
+                    MessageId::new(message_id as u64),
+                ))
+                .or_insert(snapshot_message_id);
+        }
+        self.loaded.store(true, Ordering::Release);
+
+        Ok((rows.len(), last_snapshot_message_id))
+    }
+
+    /**
+    データベースに記録された対応のうち、最後に記録されたものより新しいスナップショットメッセージのみを走査して補完します。
+    */
     pub async fn rebuild(&self, ctx: Context) {
+        let started_at = Instant::now();
+        let database = ctx.database();
         let snapshot_channel_id = ctx.app_config().await.message_logging.snapshot_channel_id;
         let bot_id = ctx.cache.current_user().id;
+
+        let (loaded_count, last_snapshot_message_id) = match self.load_index(&database).await {
+            Ok(result) => result,
+            Err(error) => {
+                error!("Failed to load message attachment snapshots from database: {error:#}");
+                return;
+            }
+        };
+
         let mut scanned_count = 0;
         let mut restored_count = 0;
         let mut messages = Box::pin(snapshot_channel_id.messages_iter(&ctx));
@@ -101,39 +324,149 @@ impl MessageSnapshotStore {
                     break;
                 }
             };
+
+            // メッセージは新しい順に取得されるため、記録済みのものに到達したら終了する
+            if last_snapshot_message_id.is_some_and(|last| message.id <= last) {
+                break;
+            }
             scanned_count += 1;
 
-            if self.restore_snapshot_id(&message, bot_id) {
-                restored_count += 1;
+            let Some((channel_id, message_id)) = Self::snapshot_source(&message, bot_id) else {
+                continue;
+            };
+
+            match self
+                .persist_if_absent(&database, channel_id, message_id, message.id)
+                .await
+            {
+                Ok(true) => restored_count += 1,
+                Ok(false) => {}
+                Err(error) => error!(
+                    "Failed to persist message attachment snapshot {}: {error:#}",
+                    message.id
+                ),
             }
         }
 
-        info!("Restored {restored_count} message attachment snapshots from {scanned_count} snapshot messages");
+        info!(
+            "Loaded {loaded_count} message attachment snapshots and restored {restored_count} from {scanned_count} snapshot messages in {:.1}s",
+            started_at.elapsed().as_secs_f64()
+        );
     }
 
-    fn restore_snapshot_id(&self, snapshot_message: &Message, bot_id: UserId) -> bool {
+    fn snapshot_source(snapshot_message: &Message, bot_id: UserId) -> Option<(GenericChannelId, MessageId)> {
         if snapshot_message.author.id != bot_id {
-            return false;
+            return None;
         }
 
-        let Some(message_reference) = snapshot_message.message_reference.as_ref() else {
-            return false;
-        };
+        let message_reference = snapshot_message.message_reference.as_ref()?;
         if message_reference.kind != MessageReferenceKind::Forward {
-            return false;
+            return None;
         }
-        let Some(message_id) = message_reference.message_id else {
-            return false;
-        };
 
-        let entry = self.snapshot_ids.entry(message_reference.channel_id).or_default();
-        match entry.entry(message_id) {
-            Entry::Occupied(_) => false,
-            Entry::Vacant(entry) => {
-                entry.insert(snapshot_message.id);
-                true
+        Some((message_reference.channel_id, message_reference.message_id?))
+    }
+
+    /**
+    保持期間を過ぎたスナップショットと、チャンネルごとの上限数を超えた古いスナップショットを削除し、削除した件数を返します。
+    */
+    pub async fn prune(
+        &self,
+        ctx: &Context,
+        max_age: Option<Duration>,
+        max_count_per_channel: Option<u32>,
+    ) -> Result<usize, AppError> {
+        let database = ctx.database();
+        let snapshot_channel_id = ctx.app_config().await.message_logging.snapshot_channel_id;
+
+        let mut expired: Vec<(i64, i64, i64)> = Vec::new();
+
+        if let Some(max_age) = max_age {
+            expired.extend(
+                sqlx::query_as::<_, (i64, i64, i64)>(
+                    "SELECT channel_id, message_id, snapshot_message_id FROM message_snapshots
+                    WHERE snapshot_message_id < $1",
+                )
+                .bind(snowflake_upper_bound(Utc::now() - max_age))
+                .fetch_all(&database)
+                .await?,
+            );
+        }
+
+        if let Some(max_count) = max_count_per_channel {
+            expired.extend(
+                sqlx::query_as::<_, (i64, i64, i64)>(
+                    "SELECT channel_id, message_id, snapshot_message_id FROM (
+                        SELECT *, ROW_NUMBER() OVER (PARTITION BY channel_id ORDER BY message_id DESC) AS rank
+                        FROM message_snapshots
+                    ) ranked WHERE rank > $1",
+                )
+                .bind(i64::from(max_count))
+                .fetch_all(&database)
+                .await?,
+            );
+        }
+
+        expired.sort_unstable();
+        expired.dedup();
+
+        let mut pruned_count = 0;
+        if let Some(local_archive) = &ctx.app_config().await.message_logging.local_archive {
+            pruned_count += self
+                .archive
+                .prune(ctx, local_archive, max_age, max_count_per_channel)
+                .await?;
+        }
+
+        for (channel_id, message_id, snapshot_message_id) in expired {
+            let snapshot_message_id = MessageId::new(snapshot_message_id as u64);
+
+            if let Err(error) = snapshot_channel_id
+                .delete_message(ctx.http(), snapshot_message_id, Some("スナップショットの保持期限切れ"))
+                .await
+                && !is_not_found(&error)
+            {
+                warn!("Failed to delete expired snapshot message {snapshot_message_id}: {error}");
+                continue;
             }
+
+            self.delete_persisted(
+                &database,
+                GenericChannelId::new(channel_id as u64),
+                MessageId::new(message_id as u64),
+            )
+            .await?;
+            pruned_count += 1;
         }
+
+        Ok(pruned_count)
+    }
+
+    pub async fn stats(&self, ctx: &Context) -> Result<SnapshotStats, AppError> {
+        let database = ctx.database();
+
+        let (total_count, channel_count, oldest_snapshot_message_id): (i64, i64, Option<i64>) = sqlx::query_as(
+            "SELECT COUNT(*), COUNT(DISTINCT channel_id), MIN(snapshot_message_id) FROM message_snapshots",
+        )
+        .fetch_one(&database)
+        .await?;
+
+        let largest_channels: Vec<(i64, i64)> = sqlx::query_as(
+            "SELECT channel_id, COUNT(*) AS count FROM message_snapshots
+            GROUP BY channel_id ORDER BY count DESC LIMIT 10",
+        )
+        .fetch_all(&database)
+        .await?;
+
+        Ok(SnapshotStats {
+            total_count,
+            channel_count,
+            oldest_snapshot_message_id: oldest_snapshot_message_id.map(|id| MessageId::new(id as u64)),
+            largest_channels: largest_channels
+                .into_iter()
+                .map(|(channel_id, count)| (GenericChannelId::new(channel_id as u64), count))
+                .collect(),
+        })
     }
 
     pub async fn attachments_for(&self, ctx: &Context, message: &Message) -> Result<FixedArray<Attachment>, AppError> {
@@ -151,23 +484,61 @@ impl MessageSnapshotStore {
         Ok(attachments)
     }
 
+    /**
+    ローカルに保存された添付ファイルを読み込みます。ローカルに保存しない設定の場合は空を返します。
+    */
+    pub async fn archived_attachments(
+        &self,
+        ctx: &Context,
+        message: &Message,
+    ) -> Result<HashMap<AttachmentId, CreateAttachment<'static>>, AppError> {
+        match &ctx.app_config().await.message_logging.local_archive {
+            Some(local_archive) => self.archive.load(ctx, local_archive, message).await,
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /**
+    削除された添付ファイルを、ローカルに保存されたものを優先し、無ければ転送または CDN から取得します。
+
+    取得できた添付ファイルのみを、元の添付ファイルの情報と組にして返します。
+    */
     pub async fn upload_attachments(
         &self,
         ctx: &Context,
         message: &Message,
         keep_attachment_ids: &[AttachmentId],
-    ) -> Result<Vec<CreateAttachment<'static>>, AppError> {
-        let attachments = self.attachments_for(ctx, message).await?;
-
-        Ok(futures::future::try_join_all(
-            attachments
-                .iter()
-                .filter(|attachment| !keep_attachment_ids.contains(&attachment.id))
-                .map(|attachment| {
-                    CreateAttachment::url(ctx.http(), attachment.url.to_string(), attachment.filename.to_string())
-                }),
-        )
-        .await?)
+    ) -> Result<Vec<(Attachment, CreateAttachment<'static>)>, AppError> {
+        let mut archived = self.archived_attachments(ctx, message).await?;
+
+        let mut snapshot_attachments = None;
+        let mut uploaded = Vec::new();
+        for attachment in message
+            .attachments
+            .iter()
+            .filter(|attachment| !keep_attachment_ids.contains(&attachment.id))
+        {
+            if let Some(file) = archived.remove(&attachment.id) {
+                uploaded.push((attachment.clone(), file));
+                continue;
+            }
+
+            // ローカルに無いものがある場合のみ、転送されたスナップショットを取得する
+            if snapshot_attachments.is_none() {
+                snapshot_attachments = Some(self.attachments_for(ctx, message).await?);
+            }
+            let url = snapshot_attachments
+                .as_ref()
+                .and_then(|snapshots| snapshots.iter().find(|snapshot| snapshot.id == attachment.id))
+                .map_or(&attachment.url, |snapshot| &snapshot.url);
+
+            match CreateAttachment::url(ctx.http(), url.to_string(), attachment.filename.to_string()).await {
+                Ok(file) => uploaded.push((attachment.clone(), file)),
+                Err(error) => warn!("Failed to download removed attachment {}: {error}", attachment.id),
+            }
+        }
+
+        Ok(uploaded)
     }
 
     pub async fn get(
@@ -176,12 +547,8 @@ impl MessageSnapshotStore {
         channel_id: GenericChannelId,
         message_id: MessageId,
     ) -> Result<Option<Message>, AppError> {
-        let snapshot_message_id = {
-            let entry = self.snapshot_ids.entry(channel_id).or_default();
-            let Some(id) = entry.get(&message_id) else {
-                return Ok(None);
-            };
-            *id
+        let Some(snapshot_message_id) = self.find(&ctx.database(), channel_id, message_id).await? else {
+            return Ok(None);
         };
 
         // URL が無効となっている可能性を考慮してメッセージを API から直接取ってくる
@@ -196,3 +563,9 @@ impl MessageSnapshotStore {
         Ok(Some(snapshot_message))
     }
 }
+
+impl SnapshotStats {
+    pub fn oldest_snapshot_timestamp(&self) -> Option<Timestamp> {
+        self.oldest_snapshot_message_id.map(|id| id.created_at())
+    }
+}
@@ -1,22 +1,25 @@
 mod admin;
 mod auth;
+mod channel_logging;
 mod honeypot;
-mod message_cache_handler;
+mod message_cache;
 mod message_logging;
 mod pin;
 mod question;
 mod thread_auto_invite;
 
-use std::borrow::Cow;
+use std::{borrow::Cow, sync::Arc};
 
 use crate::{
-    app::{AppCommand, config::AppConfig},
+    app::{AppCommand, BotData, config::AppConfig},
     core::BotEventHandlers,
     features::{
         auth::{AutoKickEventHandler, KeywordAuthEventHandler},
+        channel_logging::handle_channel_logging_event,
         honeypot::handle_honeypot_event,
-        message_cache_handler::MessageCacheHandler,
-        message_logging::MessageLoggingEventHandler,
+        message_cache::{BackfillController, MessageCacheHandler},
+        message_logging::{MessageLoggingEventHandler, MessageSnapshotStore},
+        pin::{handle_pin_reaction_event, handle_pin_request_event},
         question::handle_question_event,
         thread_auto_invite::handle_thread_auto_invite_event,
     },
@@ -26,20 +29,34 @@ pub fn event_handlers(config: &AppConfig) -> BotEventHandlers {
     BotEventHandlers::new()
         .add(handle_honeypot_event)
         .add(MessageLoggingEventHandler::new())
+        .add(handle_channel_logging_event)
         .add(handle_thread_auto_invite_event)
         .add(handle_question_event)
+        .add(handle_pin_reaction_event)
+        .add(handle_pin_request_event)
         .add(KeywordAuthEventHandler::new())
         .add(AutoKickEventHandler::new())
         .add(MessageCacheHandler::new(config.message_cache.disabled))
 }
 
+/**
+各機能で共有する状態を登録します。
+*/
+pub fn register_extensions(data: BotData) -> BotData {
+    data.with_extension(Arc::new(BackfillController::new()))
+        .with_extension(Arc::new(MessageSnapshotStore::new()))
+}
+
 pub fn commands() -> Vec<AppCommand> {
     build_commands(
         [
             auth::create_keyword_button,
             question::question,
             pin::pin,
+            pin::pin_message,
             admin::reload_config,
+            message_logging::snapshot,
+            message_cache::backfill,
             thread_auto_invite::invite_thread,
             thread_auto_invite::add_invite_role,
             thread_auto_invite::remove_invite_role,
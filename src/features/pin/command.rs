@@ -0,0 +1,291 @@
+use anyhow::Context as _;
+use poise::{CreateReply, say_reply};
+use serenity::all::{Channel, CreateAttachment, Mentionable, Message, MessageBuilder, User};
+
+use crate::{
+    app::{AppContext, AppError, BotDataExt},
+    features::pin::{
+        delegate_store::PinDelegateStore,
+        digest::{DigestFormat, MAX_MESSAGE_LENGTH, build_digest},
+        limit::{handle_pin_limit, is_pin_limit_error, pin_and_delete_notice},
+        permission::{can_pin, channel_owners, is_channel_owner},
+        request::offer_pin_request,
+    },
+    utils::{create_safe_allowed_mentions, has_authed_role},
+};
+
+/// スレッド主限定でメッセージをピン留めします。
+#[poise::command(
+    context_menu_command = "ピン留め",
+    slash_command,
+    ephemeral,
+    guild_only,
+    rename = "ピン留め",
+    required_bot_permissions = "MANAGE_MESSAGES",
+    check = "has_authed_role"
+)]
+pub async fn pin_message(
+    ctx: AppContext<'_>,
+    #[description = "ピン留めするメッセージ (リンクかID)"] msg: Message,
+) -> Result<(), AppError> {
+    toggle_pin(ctx, msg).await
+}
+
+/**
+メッセージのピン留めを切り替えます。権限が無い場合はオーナーへのリクエストを案内します。
+*/
+async fn toggle_pin(ctx: AppContext<'_>, msg: Message) -> Result<(), AppError> {
+    let config = ctx.app_config().await;
+    let channel = ctx.channel().await.unwrap();
+
+    if !can_pin(
+        ctx.serenity_context(),
+        &ctx.database(),
+        &config,
+        &channel,
+        ctx.author().id,
+    )
+    .await
+    {
+        return offer_pin_request(ctx, &config, &channel, &msg).await;
+    }
+
+    static PIN_REASON: Option<&str> = Some("/pin コマンドによる操作");
+    if msg.pinned() {
+        msg.unpin(ctx.http(), PIN_REASON)
+            .await
+            .context("Failed to unpin message")?;
+        say_reply(ctx, "ピン留めを解除しました。").await?;
+    } else {
+        match pin_and_delete_notice(ctx.serenity_context(), &msg, PIN_REASON).await {
+            Ok(()) => {
+                say_reply(ctx, "ピン留めしました。").await?;
+            }
+            Err(error) if is_pin_limit_error(&error) => handle_pin_limit(ctx, &config, &msg, PIN_REASON).await?,
+            Err(error) => return Err(error).context("Failed to pin message"),
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ピン留めの操作や、権限の委任・確認、ピン留めの一覧の作成を行います。
+
+以前の `/pin msg:` は `/pin message msg:` になりました (`/ピン留め msg:` とコンテキストメニューは従来通り使えます)。
+*/
+#[poise::command(
+    slash_command,
+    ephemeral,
+    guild_only,
+    subcommands("pin_message_subcommand", "pin_delegate", "pin_permissions", "pin_digest"),
+    subcommand_required
+)]
+pub async fn pin(_ctx: AppContext<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// スレッド主限定でメッセージをピン留めします。
+#[poise::command(
+    slash_command,
+    ephemeral,
+    guild_only,
+    rename = "message",
+    required_bot_permissions = "MANAGE_MESSAGES",
+    check = "has_authed_role"
+)]
+pub async fn pin_message_subcommand(
+    ctx: AppContext<'_>,
+    #[description = "ピン留めするメッセージ (リンクかID)"] msg: Message,
+) -> Result<(), AppError> {
+    toggle_pin(ctx, msg).await
+}
+
+/// このチャンネルでのピン留めの権限を他のメンバーに委任・取り消しします。
+#[poise::command(
+    slash_command,
+    ephemeral,
+    guild_only,
+    rename = "delegate",
+    check = "has_authed_role",
+    subcommands("pin_delegate_grant", "pin_delegate_revoke"),
+    subcommand_required
+)]
+pub async fn pin_delegate(_ctx: AppContext<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// このチャンネルでのピン留めの権限を委任します。
+#[poise::command(slash_command, ephemeral, guild_only, rename = "grant", check = "has_authed_role")]
+pub async fn pin_delegate_grant(
+    ctx: AppContext<'_>,
+    #[description = "権限を委任するメンバー"] user: User,
+) -> Result<(), AppError> {
+    let config = ctx.app_config().await;
+    let channel = ctx.channel().await.unwrap();
+
+    if !is_channel_owner(ctx.serenity_context(), &config, &channel, ctx.author().id).await {
+        say_reply(ctx, "このチャンネルのオーナーのみが権限を委任できます。").await?;
+        return Ok(());
+    }
+    if user.bot() {
+        say_reply(ctx, "Bot に権限を委任することはできません。").await?;
+        return Ok(());
+    }
+
+    let granted = PinDelegateStore::grant(&ctx.database(), channel.id(), user.id, ctx.author().id).await?;
+    let content = if granted {
+        format!("{} にピン留めの権限を委任しました。", user.id.mention())
+    } else {
+        format!("{} には既に権限が委任されています。", user.id.mention())
+    };
+
+    say_reply(ctx, content).await?;
+    Ok(())
+}
+
+/// このチャンネルでのピン留めの権限の委任を取り消します。
+#[poise::command(slash_command, ephemeral, guild_only, rename = "revoke", check = "has_authed_role")]
+pub async fn pin_delegate_revoke(
+    ctx: AppContext<'_>,
+    #[description = "権限の委任を取り消すメンバー"] user: User,
+) -> Result<(), AppError> {
+    let config = ctx.app_config().await;
+    let channel = ctx.channel().await.unwrap();
+
+    if !is_channel_owner(ctx.serenity_context(), &config, &channel, ctx.author().id).await {
+        say_reply(ctx, "このチャンネルのオーナーのみが委任を取り消せます。").await?;
+        return Ok(());
+    }
+
+    let revoked = PinDelegateStore::revoke(&ctx.database(), channel.id(), user.id).await?;
+    let content = if revoked {
+        format!("{} へのピン留めの権限の委任を取り消しました。", user.id.mention())
+    } else {
+        format!("{} には権限が委任されていません。", user.id.mention())
+    };
+
+    say_reply(ctx, content).await?;
+    Ok(())
+}
+
+/// このチャンネルでピン留めできるメンバーを表示します。
+#[poise::command(
+    slash_command,
+    ephemeral,
+    guild_only,
+    rename = "permissions",
+    check = "has_authed_role"
+)]
+pub async fn pin_permissions(ctx: AppContext<'_>) -> Result<(), AppError> {
+    let config = ctx.app_config().await;
+    let channel = ctx.channel().await.unwrap();
+
+    let owners = channel_owners(ctx.serenity_context(), &config, &channel).await;
+    let delegates = PinDelegateStore::list(&ctx.database(), channel.id()).await?;
+
+    let mut builder = MessageBuilder::new().push_bold_line("オーナー:");
+    if owners.user_ids.is_empty() && owners.role_ids.is_empty() {
+        builder = builder.push_line("- なし");
+    }
+    for owner_id in owners.user_ids {
+        builder = builder.push_line(format!("- {}", owner_id.mention()).as_str());
+    }
+    for role_id in owners.role_ids {
+        builder = builder.push_line(format!("- {} のメンバー", role_id.mention()).as_str());
+    }
+
+    builder = builder.push_bold_line("委任されたメンバー:");
+    if delegates.is_empty() {
+        builder = builder.push_line("- なし");
+    }
+    for delegate in delegates {
+        builder = builder.push_line(
+            format!(
+                "- {} ({} が委任)",
+                delegate.user_id.mention(),
+                delegate.granted_by.mention()
+            )
+            .as_str(),
+        );
+    }
+
+    say_reply(ctx, builder.build()).await?;
+    Ok(())
+}
+
+/// このチャンネルのピン留めの一覧をまとめます。質問を解決済みにしたときは自動で投稿されます。
+#[poise::command(slash_command, guild_only, rename = "digest")]
+pub async fn pin_digest(
+    ctx: AppContext<'_>,
+    #[description = "出力形式 (省略時はメッセージ、長すぎる場合はファイル)"] format: Option<DigestFormat>,
+    #[description = "チャンネルの全員に表示するかどうか (省略時は自分のみ)"] public: Option<bool>,
+) -> Result<(), AppError> {
+    let config = ctx.app_config().await;
+    let channel = ctx.channel().await.unwrap();
+    let channel_id = channel.id();
+
+    // 一覧はオーナーなどピン留めを管理するメンバー向けのため、ピン留めの権限で判定する
+    if !can_pin(
+        ctx.serenity_context(),
+        &ctx.database(),
+        &config,
+        &channel,
+        ctx.author().id,
+    )
+    .await
+    {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("このチャンネルでピン留めできるメンバーのみが一覧を作成できます。"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let channel_name = match &channel {
+        Channel::Guild(channel) => channel.base.name.to_string(),
+        Channel::GuildThread(thread) => thread.base.name.to_string(),
+        _ => channel_id.to_string(),
+    };
+
+    let public = public.unwrap_or(false);
+    if public {
+        ctx.defer().await?;
+    } else {
+        ctx.defer_ephemeral().await?;
+    }
+
+    let pins = channel_id
+        .pins(ctx.http())
+        .await
+        .context("Failed to get pinned messages")?;
+    if pins.is_empty() {
+        say_reply(ctx, "このチャンネルにはピン留めされたメッセージがありません。").await?;
+        return Ok(());
+    }
+
+    let mut format = format.unwrap_or(DigestFormat::Message);
+    let mut digest = build_digest(channel_id, &channel_name, &pins, format);
+    if format == DigestFormat::Message && digest.chars().count() > MAX_MESSAGE_LENGTH {
+        format = DigestFormat::File;
+        digest = build_digest(channel_id, &channel_name, &pins, format);
+    }
+
+    let reply = CreateReply::default()
+        .ephemeral(!public)
+        .allowed_mentions(create_safe_allowed_mentions());
+    let reply = match format {
+        DigestFormat::Message => reply.content(digest),
+        DigestFormat::File => reply
+            .content(format!("{} のピン留め ({}件)", channel_id.mention(), pins.len()))
+            .attachment(CreateAttachment::bytes(
+                digest.into_bytes(),
+                format!("pins-{channel_id}.md"),
+            )),
+    };
+
+    ctx.send(reply).await.context("Failed to send pin digest")?;
+    Ok(())
+}
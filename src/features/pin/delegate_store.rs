@@ -0,0 +1,85 @@
+use serenity::model::id::{GenericChannelId, UserId};
+use sqlx::PgPool;
+
+use crate::app::AppError;
+
+/**
+チャンネルごとにピン留めの権限を委任されたユーザー
+*/
+pub(in crate::features::pin) struct PinDelegate {
+    pub user_id: UserId,
+    pub granted_by: UserId,
+}
+
+/**
+チャンネルのオーナーが委任したピン留めの権限を永続化します。
+*/
+pub(in crate::features::pin) struct PinDelegateStore;
+
+impl PinDelegateStore {
+    pub async fn is_delegate(
+        database: &PgPool,
+        channel_id: GenericChannelId,
+        user_id: UserId,
+    ) -> Result<bool, AppError> {
+        let (exists,): (bool,) =
+            sqlx::query_as("SELECT EXISTS (SELECT 1 FROM pin_delegates WHERE channel_id = $1 AND user_id = $2)")
+                .bind(channel_id.get() as i64)
+                .bind(user_id.get() as i64)
+                .fetch_one(database)
+                .await?;
+
+        Ok(exists)
+    }
+
+    /**
+    権限を委任します。既に委任されている場合は `false` を返します。
+    */
+    pub async fn grant(
+        database: &PgPool,
+        channel_id: GenericChannelId,
+        user_id: UserId,
+        granted_by: UserId,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "INSERT INTO pin_delegates (channel_id, user_id, granted_by) VALUES ($1, $2, $3)
+            ON CONFLICT (channel_id, user_id) DO NOTHING",
+        )
+        .bind(channel_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .bind(granted_by.get() as i64)
+        .execute(database)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /**
+    委任を取り消します。委任されていなかった場合は `false` を返します。
+    */
+    pub async fn revoke(database: &PgPool, channel_id: GenericChannelId, user_id: UserId) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM pin_delegates WHERE channel_id = $1 AND user_id = $2")
+            .bind(channel_id.get() as i64)
+            .bind(user_id.get() as i64)
+            .execute(database)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list(database: &PgPool, channel_id: GenericChannelId) -> Result<Vec<PinDelegate>, AppError> {
+        let rows: Vec<(i64, i64)> =
+            sqlx::query_as("SELECT user_id, granted_by FROM pin_delegates WHERE channel_id = $1 ORDER BY created_at")
+                .bind(channel_id.get() as i64)
+                .fetch_all(database)
+                .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(user_id, granted_by)| PinDelegate {
+                user_id: UserId::new(user_id as u64),
+                granted_by: UserId::new(granted_by as u64),
+            })
+            .collect())
+    }
+}
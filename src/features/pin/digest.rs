@@ -0,0 +1,179 @@
+use anyhow::Context as _;
+use serenity::{
+    all::{CacheHttp, Context, CreateAttachment, Mentionable, Message, MessageBuilder},
+    model::{channel::GuildThread, id::GenericChannelId},
+};
+
+use crate::{app::AppError, extensions::MessageBuilderTimestampExt, utils::create_message};
+
+/// メッセージの本文の最大文字数
+pub(in crate::features::pin) const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// メッセージ形式で表示する本文の最大文字数
+const MAX_MESSAGE_EXCERPT_LENGTH: usize = 300;
+
+/**
+ピン留めの一覧の出力形式
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum DigestFormat {
+    #[name = "メッセージ"]
+    Message,
+    #[name = "Markdown ファイル"]
+    File,
+}
+
+fn excerpt(content: &str, max_length: usize) -> String {
+    if content.chars().count() > max_length {
+        let mut excerpt = content.chars().take(max_length).collect::<String>();
+        excerpt.push('…');
+        excerpt
+    } else {
+        content.to_owned()
+    }
+}
+
+/**
+Markdown の書式・マスクリンク・メンションとして解釈される文字をエスケープします。
+
+ピン留めの本文や名前をそのまま埋め込むと、一覧の見出しや引用の構造が崩れるためです。
+*/
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        if matches!(
+            char,
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#' | '-' | '[' | ']' | '(' | ')' | '<' | '@'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+    escaped
+}
+
+/**
+ピン留めされたメッセージの一覧を、古いものから順に作成者・日時・リンク・本文付きでまとめます。
+
+メッセージ形式では Discord のメンションとタイムスタンプを使い、本文を省略します。
+ファイル形式では Discord の外でも読めるよう、名前と日時を文字列で書き出します。
+本文や名前は書式として解釈されないようエスケープします。
+*/
+pub(in crate::features::pin) fn build_digest(
+    channel_id: GenericChannelId,
+    channel_name: &str,
+    pins: &[Message],
+    format: DigestFormat,
+) -> String {
+    let mut builder = MessageBuilder::new();
+    builder = match format {
+        DigestFormat::Message => {
+            builder.push_line(format!("## {} のピン留め ({}件)", channel_id.mention(), pins.len()).as_str())
+        }
+        DigestFormat::File => {
+            builder.push_line(format!("# {} のピン留め ({}件)", escape_markdown(channel_name), pins.len()).as_str())
+        }
+    };
+
+    // ピン留めは新しい順に並んでいるため、古い順に並べ替える
+    for (index, pin) in pins.iter().rev().enumerate() {
+        builder = builder.push_line("");
+        builder = match format {
+            DigestFormat::Message => builder
+                .push(format!("### {}. {} ", index + 1, pin.author.id.mention()).as_str())
+                .push_long_date_short_timestamp_line(pin.timestamp),
+            DigestFormat::File => builder.push_line(
+                format!(
+                    "## {}. {} ({})",
+                    index + 1,
+                    escape_markdown(&pin.author.name),
+                    pin.timestamp.to_rfc3339().unwrap_or_default()
+                )
+                .as_str(),
+            ),
+        };
+        builder = builder.push_line(pin.link().as_str());
+
+        let content = match format {
+            DigestFormat::Message => excerpt(&pin.content, MAX_MESSAGE_EXCERPT_LENGTH),
+            DigestFormat::File => pin.content.to_string(),
+        };
+        for line in content.lines() {
+            builder = builder.push_line(format!("> {}", escape_markdown(line)).as_str());
+        }
+        for attachment in &pin.attachments {
+            builder = builder.push_line(
+                format!(
+                    "- 添付ファイル: [{}]({})",
+                    escape_markdown(&attachment.filename),
+                    attachment.url
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    builder.build()
+}
+
+/**
+解決済みにした質問スレッドに、ピン留めの一覧を投稿します。
+
+ピン留めがない場合は何もしません。メッセージ形式で長すぎる場合は Markdown ファイルで投稿します。
+*/
+pub async fn post_solved_pin_digest(ctx: &Context, thread: &GuildThread) -> Result<(), AppError> {
+    let channel_id = thread.id.widen();
+    let pins = channel_id
+        .pins(ctx.http())
+        .await
+        .context("Failed to get pinned messages")?;
+    if pins.is_empty() {
+        return Ok(());
+    }
+
+    let channel_name = thread.base.name.as_str();
+    let digest = build_digest(channel_id, channel_name, &pins, DigestFormat::Message);
+    let message = if digest.chars().count() > MAX_MESSAGE_LENGTH {
+        create_message(format!("{} のピン留め ({}件)", channel_id.mention(), pins.len())).add_file(
+            CreateAttachment::bytes(
+                build_digest(channel_id, channel_name, &pins, DigestFormat::File).into_bytes(),
+                format!("pins-{channel_id}.md"),
+            ),
+        )
+    } else {
+        create_message(digest)
+    };
+
+    channel_id
+        .send_message(ctx.http(), message)
+        .await
+        .context("Failed to send solved pin digest")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_markdown_escapes_formatting_links_and_mentions() {
+        assert_eq!(escape_markdown("**太字** `code`"), "\\*\\*太字\\*\\* \\`code\\`");
+        assert_eq!(
+            escape_markdown("[リンク](https://example.com)"),
+            "\\[リンク\\]\\(https://example.com\\)"
+        );
+        assert_eq!(escape_markdown("@everyone <@123>"), "\\@everyone \\<\\@123>");
+        assert_eq!(escape_markdown("# 見出し"), "\\# 見出し");
+    }
+
+    #[test]
+    fn escape_markdown_keeps_plain_text() {
+        assert_eq!(escape_markdown("普通の本文です。"), "普通の本文です。");
+    }
+
+    #[test]
+    fn excerpt_truncates_long_content() {
+        assert_eq!(excerpt("あいうえお", 3), "あいう…");
+        assert_eq!(excerpt("あいう", 3), "あいう");
+    }
+}
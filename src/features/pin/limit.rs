@@ -0,0 +1,326 @@
+use std::time::Duration;
+
+use anyhow::{Context as _, anyhow};
+use futures::StreamExt;
+use poise::{CreateReply, ReplyHandle, say_reply};
+use serenity::{
+    Error as SerenityError,
+    all::{
+        ComponentInteractionCollector, ComponentInteractionDataKind, Context, CreateActionRow, CreateSelectMenu,
+        CreateSelectMenuKind, CreateSelectMenuOption, Mentionable, Message, MessageBuilder, MessageId, MessageType,
+        UserId, prelude::CacheHttp,
+    },
+    builder::CreateComponent,
+    collector::CollectMessages,
+    http::HttpError,
+};
+use tracing::{info, warn};
+
+use crate::{
+    app::{
+        AppContext, AppError,
+        config::{AppConfig, PinLimitBehavior},
+        utils::log_message::{LogMessage, send_log_message},
+    },
+    utils::channel_ancestor_ids,
+};
+
+/// ピン留めの上限に達した場合のエラーコード
+const MAX_PINS_REACHED_ERROR_CODE: isize = 30003;
+
+/// 置き換えるピン留めの選択を待つ時間
+const SELECT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// ピン留めの通知メッセージを待つ時間
+const NOTICE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// セレクトメニューの選択肢の最大数
+const MAX_SELECT_OPTIONS: usize = 25;
+
+/// セレクトメニューの選択肢のラベルの最大文字数
+const MAX_OPTION_LABEL_LENGTH: usize = 100;
+
+pub(in crate::features::pin) fn is_pin_limit_error(error: &SerenityError) -> bool {
+    matches!(
+        error,
+        SerenityError::Http(HttpError::UnsuccessfulRequest(response))
+            if response.error.code == MAX_PINS_REACHED_ERROR_CODE
+    )
+}
+
+fn option_label(message: &Message) -> String {
+    let content = if message.content.is_empty() {
+        "(本文なし)"
+    } else {
+        message.content.as_str()
+    };
+    let label = format!("{}: {}", message.author.name, content.replace('\n', " "));
+
+    if label.chars().count() > MAX_OPTION_LABEL_LENGTH {
+        let mut label = label.chars().take(MAX_OPTION_LABEL_LENGTH - 1).collect::<String>();
+        label.push('…');
+        label
+    } else {
+        label
+    }
+}
+
+/**
+メッセージをピン留めし、ピン留めの通知メッセージを削除します。
+*/
+pub(in crate::features::pin) async fn pin_and_delete_notice(
+    ctx: &Context,
+    msg: &Message,
+    reason: Option<&str>,
+) -> Result<(), SerenityError> {
+    // 置き換えの選択を待つ間に待ち受けが終了しないよう、ピン留めの直前に開始する
+    let mut stream = msg
+        .channel_id
+        .collect_messages(ctx)
+        .timeout(NOTICE_TIMEOUT)
+        .channel_id(msg.channel_id)
+        .author_id(ctx.cache.current_user().id)
+        .filter(|r| r.kind == MessageType::PinsAdd)
+        .stream();
+
+    msg.pin(ctx.http(), reason).await?;
+
+    if let Some(notice) = stream.next().await {
+        let _ = notice.delete(ctx.http(), None).await;
+    }
+
+    Ok(())
+}
+
+/**
+上限によりピン留めを解除したことをログに送信します。
+*/
+async fn log_unpinned(ctx: &Context, config: &AppConfig, unpinned: &Message, pinned: &Message, operator_id: UserId) {
+    info!(
+        "Unpinned message {} in {} to make room for {}",
+        unpinned.id, unpinned.channel_id, pinned.id
+    );
+
+    let Some(destination) = config.pin.log_destination() else {
+        return;
+    };
+
+    let content = MessageBuilder::new()
+        .push_line(
+            format!(
+                "{} のピン留めが上限に達したため、ピン留めを置き換えました。",
+                unpinned.channel_id.mention()
+            )
+            .as_str(),
+        )
+        .push_line(format!("解除: {} (作成者: {})", unpinned.link(), unpinned.author.id.mention()).as_str())
+        .push_line(format!("追加: {}", pinned.link()).as_str())
+        .push_line(format!("操作: {}", operator_id.mention()).as_str())
+        .build();
+
+    if let Err(error) = send_log_message(ctx, destination, LogMessage::content(content)).await {
+        warn!("Failed to send pin log: {error:#}");
+    }
+}
+
+/**
+ピン留めを解除してから、新たにメッセージをピン留めします。
+*/
+async fn replace_pin(
+    ctx: &Context,
+    config: &AppConfig,
+    unpinned: &Message,
+    msg: &Message,
+    reason: Option<&str>,
+    operator_id: UserId,
+) -> Result<(), AppError> {
+    unpinned
+        .unpin(ctx.http(), reason)
+        .await
+        .context("Failed to unpin message")?;
+    pin_and_delete_notice(ctx, msg, reason)
+        .await
+        .context("Failed to pin message")?;
+
+    log_unpinned(ctx, config, unpinned, msg, operator_id).await;
+    Ok(())
+}
+
+/**
+最も古いピン留めを解除してから、新たにメッセージをピン留めします。解除したピン留めを返します。
+*/
+async fn unpin_oldest_and_pin<'a>(
+    ctx: &Context,
+    config: &AppConfig,
+    pins: &'a [Message],
+    msg: &Message,
+    reason: Option<&str>,
+    operator_id: UserId,
+) -> Result<&'a Message, AppError> {
+    // ピン留めは新しい順に並んでいる
+    let oldest = pins.last().ok_or_else(|| anyhow!("No pinned messages to unpin"))?;
+    replace_pin(ctx, config, oldest, msg, reason, operator_id).await?;
+    Ok(oldest)
+}
+
+async fn unpin_oldest(
+    ctx: AppContext<'_>,
+    config: &AppConfig,
+    pins: &[Message],
+    msg: &Message,
+    reason: Option<&str>,
+) -> Result<(), AppError> {
+    let oldest = unpin_oldest_and_pin(ctx.serenity_context(), config, pins, msg, reason, ctx.author().id).await?;
+
+    say_reply(
+        ctx,
+        format!(
+            "ピン留めの上限に達したため、最も古いピン留め ({}) を解除してピン留めしました。",
+            oldest.link()
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn select_pin_to_replace(
+    ctx: AppContext<'_>,
+    config: &AppConfig,
+    pins: &[Message],
+    msg: &Message,
+    reason: Option<&str>,
+) -> Result<(), AppError> {
+    let custom_id = format!("pin_replace:{}", ctx.id());
+
+    // 古いものから順に、置き換えの候補として表示する
+    let options = pins
+        .iter()
+        .rev()
+        .take(MAX_SELECT_OPTIONS)
+        .map(|pin| CreateSelectMenuOption::new(option_label(pin), pin.id.to_string()))
+        .collect::<Vec<_>>();
+    let select_menu = CreateSelectMenu::new(
+        custom_id.as_str(),
+        CreateSelectMenuKind::String {
+            options: options.into(),
+        },
+    )
+    .placeholder("解除するピン留めを選択してください");
+
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content("ピン留めの上限に達しています。置き換えるピン留めを選択してください。")
+                .components(vec![CreateComponent::ActionRow(CreateActionRow::select_menu(
+                    select_menu,
+                ))]),
+        )
+        .await
+        .context("Failed to send pin replacement prompt")?;
+
+    let interaction = ComponentInteractionCollector::new(ctx.serenity_context())
+        .filter(move |i| i.data.custom_id.as_str() == custom_id)
+        .author_id(ctx.author().id)
+        .timeout(SELECT_TIMEOUT)
+        .stream()
+        .next()
+        .await;
+
+    let Some(interaction) = interaction else {
+        edit_reply(ctx, &reply, "時間切れのため、ピン留めを中止しました。").await?;
+        return Ok(());
+    };
+    let _ = interaction.defer(ctx.http()).await;
+
+    let selected = match &interaction.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } => values
+            .first()
+            .and_then(|value| value.parse::<MessageId>().ok())
+            .and_then(|id| pins.iter().find(|pin| pin.id == id)),
+        _ => None,
+    };
+    let Some(selected) = selected else {
+        edit_reply(ctx, &reply, "選択されたピン留めが見つかりませんでした。").await?;
+        return Ok(());
+    };
+
+    replace_pin(ctx.serenity_context(), config, selected, msg, reason, ctx.author().id).await?;
+    edit_reply(
+        ctx,
+        &reply,
+        &format!("{} のピン留めを解除してピン留めしました。", selected.link()),
+    )
+    .await
+}
+
+async fn edit_reply(ctx: AppContext<'_>, reply: &ReplyHandle<'_>, content: &str) -> Result<(), AppError> {
+    reply
+        .edit(ctx, CreateReply::default().content(content).components(vec![]))
+        .await
+        .context("Failed to edit pin replacement prompt")?;
+    Ok(())
+}
+
+/**
+コマンド以外の操作でメッセージをピン留めし、ピン留めの通知メッセージを削除します。
+
+選択肢を提示できないため、ピン留めの上限に達している場合は最も古いピン留めを解除する設定のチャンネルでのみ置き換えます。
+ピン留めしなかった場合は `false` を返します。
+*/
+pub(in crate::features::pin) async fn pin_without_prompt(
+    ctx: &Context,
+    config: &AppConfig,
+    message: &Message,
+    reason: Option<&str>,
+    operator_id: UserId,
+) -> Result<bool, AppError> {
+    match pin_and_delete_notice(ctx, message, reason).await {
+        Ok(()) => {}
+        Err(error) if is_pin_limit_error(&error) => {
+            let ancestor_ids = channel_ancestor_ids(ctx, message.guild_id, message.channel_id);
+            if config.pin.limit_behavior_for(&ancestor_ids) != PinLimitBehavior::UnpinOldest {
+                warn!(
+                    "Pin limit reached in {}, skipped pinning {}",
+                    message.channel_id, message.id
+                );
+                return Ok(false);
+            }
+
+            let pins = message
+                .channel_id
+                .pins(ctx.http())
+                .await
+                .context("Failed to get pinned messages")?;
+            unpin_oldest_and_pin(ctx, config, &pins, message, reason, operator_id).await?;
+        }
+        Err(error) => return Err(error).context("Failed to pin message"),
+    }
+    info!(
+        "Pinned message {} in {} by {operator_id}",
+        message.id, message.channel_id
+    );
+
+    Ok(true)
+}
+
+/**
+ピン留めの上限に達した場合に、チャンネルの設定に従って既存のピン留めを置き換えます。
+*/
+pub(in crate::features::pin) async fn handle_pin_limit(
+    ctx: AppContext<'_>,
+    config: &AppConfig,
+    msg: &Message,
+    reason: Option<&str>,
+) -> Result<(), AppError> {
+    let pins = msg
+        .channel_id
+        .pins(ctx.http())
+        .await
+        .context("Failed to get pinned messages")?;
+
+    let ancestor_ids = channel_ancestor_ids(ctx.serenity_context(), ctx.guild_id(), msg.channel_id);
+    match config.pin.limit_behavior_for(&ancestor_ids) {
+        PinLimitBehavior::UnpinOldest => unpin_oldest(ctx, config, &pins, msg, reason).await,
+        PinLimitBehavior::Select => select_pin_to_replace(ctx, config, &pins, msg, reason).await,
+    }
+}
@@ -0,0 +1,13 @@
+mod command;
+mod delegate_store;
+mod digest;
+mod limit;
+mod permission;
+mod reaction;
+mod request;
+mod request_store;
+
+pub use command::{pin, pin_message};
+pub use digest::post_solved_pin_digest;
+pub use reaction::handle_pin_reaction_event;
+pub use request::handle_pin_request_event;
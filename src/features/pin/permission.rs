@@ -0,0 +1,151 @@
+use std::iter;
+
+use serenity::{
+    all::Context,
+    model::{
+        channel::{Channel, GuildThread},
+        id::{GenericChannelId, GuildId, MessageId, UserId},
+    },
+};
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::{
+    app::config::{AppConfig, PinChannelOwners},
+    features::pin::delegate_store::PinDelegateStore,
+    utils::channel_ancestor_ids,
+};
+
+fn channel_guild_id(channel: &Channel) -> Option<GuildId> {
+    match channel {
+        Channel::Guild(channel) => Some(channel.base.guild_id),
+        Channel::GuildThread(thread) => Some(thread.base.guild_id),
+        _ => None,
+    }
+}
+
+/**
+チャンネル自身から親チャンネル・カテゴリの順にIDを返します。
+
+アーカイブされたスレッドはキャッシュに無いため、取得したチャンネルの親から辿ります。
+*/
+fn pin_ancestor_ids(ctx: &Context, channel: &Channel) -> Vec<GenericChannelId> {
+    let parent_id = match channel {
+        Channel::Guild(channel) => channel.parent_id.map(|id| id.widen()),
+        Channel::GuildThread(thread) => Some(thread.parent_id.widen()),
+        _ => None,
+    };
+
+    iter::once(channel.id())
+        .chain(parent_id.map_or_else(Vec::new, |parent_id| {
+            channel_ancestor_ids(ctx, channel_guild_id(channel), parent_id)
+        }))
+        .collect()
+}
+
+/**
+質問フォーラムのスレッドの場合、初期メッセージでメンションされたユーザー (質問者) を返します。
+*/
+async fn question_author_ids(ctx: &Context, config: &AppConfig, thread: &GuildThread) -> Vec<UserId> {
+    if config.question.forum(thread.parent_id).is_none() {
+        return Vec::new();
+    }
+
+    // スレッドの初期メッセージのIDはスレッドのIDと同じ
+    // メッセージが取得できない場合はスレッドオーナーではない判定
+    match thread.id.widen().message(ctx, MessageId::new(thread.id.get())).await {
+        Ok(msg) => msg.mentions.iter().map(|user| user.id).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/**
+チャンネルのオーナーを列挙します。
+
+コンフィグでチャンネル・親チャンネル・カテゴリに設定されたオーナー、スレッドの作成者、質問フォーラムの場合は初期メッセージでメンションされたユーザーが該当します。
+*/
+pub(in crate::features::pin) async fn channel_owners(
+    ctx: &Context,
+    config: &AppConfig,
+    channel: &Channel,
+) -> PinChannelOwners {
+    // コンフィグで設定されたオーナー
+    let ancestor_ids = pin_ancestor_ids(ctx, channel);
+    let mut owners = config.pin.owners_for(&ancestor_ids);
+
+    let Channel::GuildThread(channel) = channel else {
+        return owners;
+    };
+
+    for user_id in iter::once(channel.owner_id).chain(question_author_ids(ctx, config, channel).await) {
+        if !owners.user_ids.contains(&user_id) {
+            owners.user_ids.push(user_id);
+        }
+    }
+
+    owners
+}
+
+/**
+ユーザーがチャンネルのオーナーかどうかを判定します。オーナーのみがピン留めの権限を委任できます。
+*/
+pub(in crate::features::pin) async fn is_channel_owner(
+    ctx: &Context,
+    config: &AppConfig,
+    channel: &Channel,
+    user_id: UserId,
+) -> bool {
+    // コンフィグで設定されたオーナーかどうか
+    let ancestor_ids = pin_ancestor_ids(ctx, channel);
+    let owners = config.pin.owners_for(&ancestor_ids);
+    if owners.user_ids.contains(&user_id) {
+        return true;
+    }
+
+    if !owners.role_ids.is_empty()
+        && let Some(guild_id) = channel_guild_id(channel)
+    {
+        match guild_id.member(ctx, user_id).await {
+            Ok(member) => {
+                if member.roles.iter().any(|role_id| owners.role_ids.contains(role_id)) {
+                    return true;
+                }
+            }
+            Err(error) => warn!("Failed to get member {user_id} to check pin owner roles: {error}"),
+        }
+    }
+
+    let Channel::GuildThread(channel) = channel else {
+        return false;
+    };
+
+    if channel.owner_id == user_id {
+        return true;
+    }
+
+    // 質問フォーラムの場合、初期メッセージのメンションからスレッド主を取得
+    question_author_ids(ctx, config, channel).await.contains(&user_id)
+}
+
+/**
+ユーザーがチャンネルでピン留めできるかどうかを判定します。
+*/
+pub(in crate::features::pin) async fn can_pin(
+    ctx: &Context,
+    database: &PgPool,
+    config: &AppConfig,
+    channel: &Channel,
+    user_id: UserId,
+) -> bool {
+    if is_channel_owner(ctx, config, channel, user_id).await {
+        return true;
+    }
+
+    match PinDelegateStore::is_delegate(database, channel.id(), user_id).await {
+        Ok(is_delegate) => is_delegate,
+        Err(error) => {
+            warn!("Failed to check pin delegate: {error:#}");
+            false
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::Context as _;
+use serenity::{
+    all::{Context, Message, prelude::CacheHttp},
+    model::{
+        channel::{Channel, GenericGuildChannelRef, Reaction, ReactionType},
+        event::FullEvent,
+        id::{GenericChannelId, GuildId, MessageId, RoleId, UserId},
+    },
+};
+use valine_bot_macros::event_handler;
+
+use crate::{
+    app::{AppError, BotDataExt, config::AppConfig},
+    features::pin::{limit::pin_without_prompt, permission::can_pin},
+    utils::channel_ancestor_ids,
+};
+
+/// リアクション数を数える際に一度に取得するユーザー数
+const REACTION_USERS_PAGE_SIZE: u8 = 100;
+
+static PIN_REASON: Option<&str> = Some("リアクションによる操作");
+static THRESHOLD_PIN_REASON: Option<&str> = Some("リアクション数による操作");
+
+fn is_same_emoji(a: &ReactionType, b: &ReactionType) -> bool {
+    match (a, b) {
+        (ReactionType::Custom { id: a, .. }, ReactionType::Custom { id: b, .. }) => a == b,
+        (ReactionType::Unicode(a), ReactionType::Unicode(b)) => a == b,
+        _ => false,
+    }
+}
+
+/**
+メッセージをキャッシュから取得し、無い場合は API から取得します。
+*/
+async fn fetch_message(
+    ctx: &Context,
+    channel_id: GenericChannelId,
+    message_id: MessageId,
+) -> Result<Message, AppError> {
+    if let Some(message) = ctx.cache.message(channel_id, message_id) {
+        return Ok(message.clone());
+    }
+
+    channel_id
+        .message(ctx, message_id)
+        .await
+        .context("Failed to get reacted message")
+}
+
+/**
+チャンネルをキャッシュから取得し、無い場合 (アーカイブされたスレッドなど) は API から取得します。
+*/
+async fn fetch_channel(ctx: &Context, guild_id: GuildId, channel_id: GenericChannelId) -> Result<Channel, AppError> {
+    let cached = guild_id
+        .to_guild_cached(&ctx.cache)
+        .and_then(|guild| match guild.channel(channel_id)? {
+            GenericGuildChannelRef::Channel(channel) => Some(Channel::Guild(channel.clone())),
+            GenericGuildChannelRef::Thread(thread) => Some(Channel::GuildThread(thread.clone())),
+        });
+    if let Some(channel) = cached {
+        return Ok(channel);
+    }
+
+    ctx.http
+        .get_channel(channel_id)
+        .await
+        .context("Failed to get reacted channel")
+}
+
+/**
+メンバーがロールを持っているかを、キャッシュにある場合はキャッシュから判定します。
+*/
+async fn member_has_role(ctx: &Context, guild_id: GuildId, user_id: UserId, role_id: RoleId) -> bool {
+    let cached = guild_id.to_guild_cached(&ctx.cache).and_then(|guild| {
+        guild
+            .members
+            .get(&user_id)
+            .map(|member| member.roles.contains(&role_id))
+    });
+    if let Some(has_role) = cached {
+        return has_role;
+    }
+
+    guild_id
+        .member(ctx, user_id)
+        .await
+        .is_ok_and(|member| member.roles.contains(&role_id))
+}
+
+/**
+リアクションした認証済みメンバーの数を、閾値に達するまで数えます。
+*/
+async fn count_authed_reactions(
+    ctx: &Context,
+    config: &AppConfig,
+    guild_id: GuildId,
+    message: &Message,
+    emoji: &ReactionType,
+    threshold: usize,
+) -> Result<usize, AppError> {
+    let mut count = 0;
+    let mut after = None;
+
+    loop {
+        let users = ctx
+            .http
+            .get_reaction_users(message.channel_id, message.id, emoji, REACTION_USERS_PAGE_SIZE, after)
+            .await
+            .context("Failed to get reaction users")?;
+
+        for user in &users {
+            if user.bot() {
+                continue;
+            }
+
+            if member_has_role(ctx, guild_id, user.id, config.auth.role_id).await {
+                count += 1;
+                if count >= threshold {
+                    return Ok(count);
+                }
+            }
+        }
+
+        match users.last() {
+            Some(last) if users.len() == usize::from(REACTION_USERS_PAGE_SIZE) => after = Some(last.id),
+            _ => return Ok(count),
+        }
+    }
+}
+
+async fn handle_reaction_add(ctx: &Context, reaction: &Reaction) -> Result<(), AppError> {
+    let config = ctx.app_config().await;
+
+    let Some(emoji) = &config.pin.reaction_emoji else {
+        return Ok(());
+    };
+    let (Some(guild_id), Some(user_id)) = (reaction.guild_id, reaction.user_id) else {
+        return Ok(());
+    };
+    if !is_same_emoji(&reaction.emoji, emoji) || user_id == ctx.cache.current_user().id {
+        return Ok(());
+    }
+
+    let message = fetch_message(ctx, reaction.channel_id, reaction.message_id).await?;
+    if message.pinned() {
+        return Ok(());
+    }
+
+    let channel = fetch_channel(ctx, guild_id, reaction.channel_id).await?;
+
+    if can_pin(ctx, &ctx.database(), &config, &channel, user_id).await {
+        pin_without_prompt(ctx, &config, &message, PIN_REASON, user_id).await?;
+        return Ok(());
+    }
+
+    let ancestor_ids = channel_ancestor_ids(ctx, Some(guild_id), reaction.channel_id);
+    let Some(threshold) = config.pin.reaction_threshold_for(&ancestor_ids) else {
+        return Ok(());
+    };
+
+    // キャッシュのメッセージのリアクション数は更新されないため、最新のものを取得する
+    // リアクションの総数が閾値に満たない場合は、メンバーを確認するまでもない
+    let message = reaction
+        .channel_id
+        .message(ctx.http(), reaction.message_id)
+        .await
+        .context("Failed to get reacted message")?;
+    let total = message
+        .reactions
+        .iter()
+        .find(|r| is_same_emoji(&r.reaction_type, emoji))
+        .map_or(0, |r| r.count as usize);
+    if total < threshold {
+        return Ok(());
+    }
+
+    // 特定のメンバーの操作ではないため、操作者は Bot とする
+    if count_authed_reactions(ctx, &config, guild_id, &message, emoji, threshold).await? >= threshold {
+        let bot_id = ctx.cache.current_user().id;
+        pin_without_prompt(ctx, &config, &message, THRESHOLD_PIN_REASON, bot_id).await?;
+    }
+
+    Ok(())
+}
+
+#[event_handler]
+pub async fn handle_pin_reaction_event(ctx: &Context, event: &FullEvent) -> Result<(), AppError> {
+    if let FullEvent::ReactionAdd { add_reaction, .. } = event {
+        handle_reaction_add(ctx, add_reaction).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,500 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::{Context as _, anyhow};
+use futures::StreamExt;
+use poise::{CreateReply, say_reply};
+use serenity::{
+    all::{
+        ButtonStyle, ComponentInteraction, ComponentInteractionCollector, ComponentInteractionDataKind, Context,
+        CreateActionRow, CreateButton, Interaction, Mentionable, Message, MessageBuilder, prelude::CacheHttp,
+    },
+    builder::{CreateComponent, EditInteractionResponse, EditMessage},
+    model::{
+        channel::Channel,
+        event::FullEvent,
+        id::{GenericChannelId, MessageId, UserId},
+    },
+};
+use tracing::warn;
+use valine_bot_macros::event_handler;
+
+use crate::{
+    app::{
+        AppContext, AppError, BotDataExt,
+        config::{AppConfig, PinRequestDestination},
+    },
+    features::pin::{
+        limit::pin_without_prompt,
+        permission::{can_pin, channel_owners},
+        request_store::PinRequestStore,
+    },
+    utils::{create_message, create_safe_allowed_mentions},
+};
+
+static PIN_REQUEST_PREFIX: &str = "pin_request";
+
+/// リクエストを送信するボタンが押されるのを待つ時間
+const REQUEST_BUTTON_TIMEOUT: Duration = Duration::from_secs(60);
+
+static PIN_REASON: Option<&str> = Some("ピン留めのリクエストの承認による操作");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PinRequestAction {
+    Approve,
+    Deny,
+}
+
+impl PinRequestAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            PinRequestAction::Approve => "approve",
+            PinRequestAction::Deny => "deny",
+        }
+    }
+}
+
+/**
+承認・却下のボタンに埋め込むリクエストの内容
+
+DM でも処理できるよう、チャンネルとメッセージのIDを含めます。
+*/
+#[derive(Debug, Clone, Copy)]
+pub(in crate::features::pin) struct PinRequest {
+    pub channel_id: GenericChannelId,
+    pub message_id: MessageId,
+    pub requester_id: UserId,
+}
+
+impl PinRequest {
+    fn custom_id(&self, action: PinRequestAction) -> String {
+        format!(
+            "{PIN_REQUEST_PREFIX}:{}:{}:{}:{}",
+            action.as_str(),
+            self.channel_id,
+            self.message_id,
+            self.requester_id
+        )
+    }
+
+    fn parse(custom_id: &str) -> Option<(PinRequestAction, Self)> {
+        let mut parts = custom_id
+            .strip_prefix(PIN_REQUEST_PREFIX)?
+            .strip_prefix(':')?
+            .split(':');
+
+        let action = match parts.next()? {
+            "approve" => PinRequestAction::Approve,
+            "deny" => PinRequestAction::Deny,
+            _ => return None,
+        };
+        let request = Self {
+            channel_id: GenericChannelId::from_str(parts.next()?).ok()?,
+            message_id: MessageId::from_str(parts.next()?).ok()?,
+            requester_id: UserId::from_str(parts.next()?).ok()?,
+        };
+
+        parts.next().is_none().then_some((action, request))
+    }
+
+    fn buttons<'a>(&self) -> CreateComponent<'a> {
+        CreateComponent::ActionRow(CreateActionRow::buttons(vec![
+            CreateButton::new(self.custom_id(PinRequestAction::Approve))
+                .label("承認してピン留め")
+                .style(ButtonStyle::Success),
+            CreateButton::new(self.custom_id(PinRequestAction::Deny))
+                .label("却下")
+                .style(ButtonStyle::Danger),
+        ]))
+    }
+}
+
+/**
+チャンネルのオーナーにピン留めのリクエストを送信します。
+
+同じメッセージを直近にリクエストしている場合は送信せず、`false` を返します。
+*/
+async fn send_pin_request(
+    ctx: AppContext<'_>,
+    config: &AppConfig,
+    channel: &Channel,
+    msg: &Message,
+) -> Result<bool, AppError> {
+    let database = ctx.database();
+    let request = PinRequest {
+        channel_id: msg.channel_id,
+        message_id: msg.id,
+        requester_id: ctx.author().id,
+    };
+
+    PinRequestStore::delete_expired(&database).await?;
+    if !PinRequestStore::create(&database, &request).await? {
+        return Ok(false);
+    }
+
+    // 送信に失敗した場合は、すぐに再送信できるよう記録を消す
+    if let Err(error) = send_pin_request_prompts(ctx, config, channel, msg, &request).await {
+        PinRequestStore::delete(&database, &request).await?;
+        return Err(error);
+    }
+
+    Ok(true)
+}
+
+/**
+リクエストの承認・却下のボタンを、設定された送信先のオーナーに送信します。
+*/
+async fn send_pin_request_prompts(
+    ctx: AppContext<'_>,
+    config: &AppConfig,
+    channel: &Channel,
+    msg: &Message,
+    request: &PinRequest,
+) -> Result<(), AppError> {
+    let database = ctx.database();
+    let owners = channel_owners(ctx.serenity_context(), config, channel).await;
+    if owners.user_ids.is_empty() && owners.role_ids.is_empty() {
+        return Err(anyhow!("No owners to receive the pin request"));
+    }
+
+    let content = MessageBuilder::new()
+        .push_line(
+            format!(
+                "{} が次のメッセージのピン留めをリクエストしました。",
+                request.requester_id.mention()
+            )
+            .as_str(),
+        )
+        .push_line(msg.link().as_str())
+        .build();
+
+    // ロールのみが設定されている場合はDMの送信先がないため、チャンネルに送信する
+    let destination = match config.pin.request_destination {
+        PinRequestDestination::Dm if owners.user_ids.is_empty() => PinRequestDestination::Channel,
+        destination => destination,
+    };
+    match destination {
+        PinRequestDestination::Channel => {
+            let mentions = owners
+                .user_ids
+                .iter()
+                .map(|id| id.mention().to_string())
+                .chain(owners.role_ids.iter().map(|id| id.mention().to_string()))
+                .collect::<Vec<_>>();
+            let prompt = msg
+                .channel_id
+                .send_message(
+                    ctx.http(),
+                    create_message(format!("{}\n{content}", mentions.join(" ")))
+                        .allowed_mentions(
+                            create_safe_allowed_mentions()
+                                .users(owners.user_ids)
+                                .roles(owners.role_ids),
+                        )
+                        .components(vec![request.buttons()]),
+                )
+                .await
+                .context("Failed to send pin request")?;
+            PinRequestStore::add_prompt(&database, request, prompt.channel_id, prompt.id).await?;
+        }
+        PinRequestDestination::Dm => {
+            let mut sent = false;
+            for owner_id in owners.user_ids {
+                let result = owner_id
+                    .direct_message(
+                        ctx.http(),
+                        create_message(content.as_str()).components(vec![request.buttons()]),
+                    )
+                    .await;
+                match result {
+                    Ok(prompt) => {
+                        sent = true;
+                        PinRequestStore::add_prompt(&database, request, prompt.channel_id, prompt.id).await?;
+                    }
+                    Err(error) => warn!("Failed to send pin request to {owner_id}: {error}"),
+                }
+            }
+
+            if !sent {
+                return Err(anyhow!("Failed to send pin request to any owner"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ピン留めの権限がないことを伝え、オーナーにリクエストを送信するボタンを表示します。
+*/
+pub(in crate::features::pin) async fn offer_pin_request(
+    ctx: AppContext<'_>,
+    config: &AppConfig,
+    channel: &Channel,
+    msg: &Message,
+) -> Result<(), AppError> {
+    const DENIED: &str = "あなたはこのチャンネルでピン留めできません。";
+    if msg.pinned() {
+        say_reply(ctx, DENIED).await?;
+        return Ok(());
+    }
+
+    let custom_id = format!("{PIN_REQUEST_PREFIX}_send:{}", ctx.id());
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content(format!("{DENIED}\nオーナーにピン留めをリクエストできます。"))
+                .components(vec![CreateComponent::ActionRow(CreateActionRow::buttons(vec![
+                    CreateButton::new(custom_id.as_str())
+                        .label("ピン留めをリクエスト")
+                        .style(ButtonStyle::Primary),
+                ]))]),
+        )
+        .await
+        .context("Failed to send pin request prompt")?;
+
+    let interaction = ComponentInteractionCollector::new(ctx.serenity_context())
+        .filter(move |i| i.data.custom_id.as_str() == custom_id)
+        .author_id(ctx.author().id)
+        .timeout(REQUEST_BUTTON_TIMEOUT)
+        .stream()
+        .next()
+        .await;
+
+    let content = match interaction {
+        Some(interaction) => {
+            let _ = interaction.defer(ctx.http()).await;
+            match send_pin_request(ctx, config, channel, msg).await {
+                Ok(true) => "オーナーにピン留めをリクエストしました。",
+                Ok(false) => "このメッセージは既にリクエスト済みです。しばらくしてから再度お試しください。",
+                Err(error) => {
+                    warn!("Failed to send pin request: {error:#}");
+                    "リクエストを送信できませんでした。"
+                }
+            }
+        }
+        None => DENIED,
+    };
+
+    reply
+        .edit(ctx, CreateReply::default().content(content).components(vec![]))
+        .await
+        .context("Failed to edit pin request prompt")?;
+    Ok(())
+}
+
+async fn handle_interaction_create(ctx: &Context, interaction: &Interaction) -> Result<(), AppError> {
+    let Interaction::Component(interaction) = interaction else {
+        return Ok(());
+    };
+    let ComponentInteractionDataKind::Button = interaction.data.kind else {
+        return Ok(());
+    };
+    let Some((action, request)) = PinRequest::parse(&interaction.data.custom_id) else {
+        return Ok(());
+    };
+
+    interaction
+        .defer_ephemeral(ctx.http())
+        .await
+        .context("Failed to defer pin request response")?;
+
+    let result = respond_pin_request(ctx, interaction, action, request).await;
+    if result.is_err() {
+        interaction
+            .edit_response(
+                ctx.http(),
+                EditInteractionResponse::new()
+                    .content("リクエストを処理できませんでした。時間をおいて再度お試しください。"),
+            )
+            .await?;
+    }
+
+    result
+}
+
+/**
+承認・却下の操作を処理し、リクエストのメッセージと応答を更新します。
+*/
+async fn respond_pin_request(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    action: PinRequestAction,
+    request: PinRequest,
+) -> Result<(), AppError> {
+    let config = ctx.app_config().await;
+    let channel = ctx
+        .http
+        .get_channel(request.channel_id)
+        .await
+        .context("Failed to get pin request channel")?;
+
+    let operator_id = interaction.user.id;
+    if !can_pin(ctx, &ctx.database(), &config, &channel, operator_id).await {
+        interaction
+            .edit_response(
+                ctx.http(),
+                EditInteractionResponse::new().content("あなたはこのチャンネルでピン留めできません。"),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    // 複数のオーナーに送信したリクエストを重複して処理しないよう、先に処理済みにする
+    let database = ctx.database();
+    if !PinRequestStore::resolve(&database, &request, operator_id).await? {
+        let mut prompt = (*interaction.message).clone();
+        prompt
+            .edit(&ctx, EditMessage::new().components(vec![]))
+            .await
+            .context("Failed to update pin request prompt")?;
+        interaction
+            .edit_response(
+                ctx.http(),
+                EditInteractionResponse::new().content("このリクエストは既に処理されています。"),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let result = match apply_pin_request_action(ctx, &config, action, &request, operator_id).await {
+        Ok(Some(result)) => result,
+        // ピン留めできなかった場合は、他のオーナーが再度処理できるよう未処理に戻す
+        Ok(None) => {
+            PinRequestStore::reopen(&database, &request).await?;
+            interaction
+                .edit_response(
+                    ctx.http(),
+                    EditInteractionResponse::new()
+                        .content("ピン留めの上限に達しているため、ピン留めできませんでした。"),
+                )
+                .await?;
+            return Ok(());
+        }
+        Err(error) => {
+            PinRequestStore::reopen(&database, &request).await?;
+            return Err(error);
+        }
+    };
+
+    // 他のオーナーに送信したリクエストも含めて、ボタンを取り除いて結果を表示する
+    let mut prompts = PinRequestStore::prompts(&database, &request).await?;
+    let clicked = (interaction.message.channel_id, interaction.message.id);
+    if !prompts.contains(&clicked) {
+        prompts.push(clicked);
+    }
+    for (prompt_channel_id, prompt_message_id) in prompts {
+        if let Err(error) = close_prompt(ctx, prompt_channel_id, prompt_message_id, result, operator_id).await {
+            warn!("Failed to update pin request prompt {prompt_message_id}: {error:#}");
+        }
+    }
+
+    interaction
+        .edit_response(ctx.http(), EditInteractionResponse::new().content(result))
+        .await?;
+
+    Ok(())
+}
+
+/**
+承認・却下の操作を行い、結果を返します。
+
+承認の場合はメッセージをピン留めし、ピン留めの上限に達していた場合は `None` を返します。
+*/
+async fn apply_pin_request_action(
+    ctx: &Context,
+    config: &AppConfig,
+    action: PinRequestAction,
+    request: &PinRequest,
+    operator_id: UserId,
+) -> Result<Option<&'static str>, AppError> {
+    match action {
+        PinRequestAction::Approve => {
+            let message = request
+                .channel_id
+                .message(ctx, request.message_id)
+                .await
+                .context("Failed to get requested message")?;
+
+            if message.pinned() {
+                Ok(Some("既にピン留めされています。"))
+            } else if pin_without_prompt(ctx, config, &message, PIN_REASON, operator_id).await? {
+                Ok(Some("承認されました。"))
+            } else {
+                Ok(None)
+            }
+        }
+        PinRequestAction::Deny => Ok(Some("却下されました。")),
+    }
+}
+
+/**
+リクエストのメッセージからボタンを取り除き、処理の結果と処理したオーナーを追記します。
+*/
+async fn close_prompt(
+    ctx: &Context,
+    channel_id: GenericChannelId,
+    message_id: MessageId,
+    result: &str,
+    operator_id: UserId,
+) -> Result<(), AppError> {
+    let mut prompt = channel_id.message(ctx, message_id).await?;
+    prompt
+        .edit(
+            ctx,
+            EditMessage::new()
+                .content(format!("{}\n{result} ({})", prompt.content, operator_id.mention()))
+                .allowed_mentions(create_safe_allowed_mentions())
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[event_handler]
+pub async fn handle_pin_request_event(ctx: &Context, event: &FullEvent) -> Result<(), AppError> {
+    if let FullEvent::InteractionCreate { interaction, .. } = event {
+        handle_interaction_create(ctx, interaction).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> PinRequest {
+        PinRequest {
+            channel_id: GenericChannelId::new(1),
+            message_id: MessageId::new(2),
+            requester_id: UserId::new(3),
+        }
+    }
+
+    #[test]
+    fn pin_request_round_trips_custom_id() {
+        for action in [PinRequestAction::Approve, PinRequestAction::Deny] {
+            let (parsed_action, parsed) = PinRequest::parse(&request().custom_id(action)).unwrap();
+            assert_eq!(parsed_action, action);
+            assert_eq!(parsed.channel_id, GenericChannelId::new(1));
+            assert_eq!(parsed.message_id, MessageId::new(2));
+            assert_eq!(parsed.requester_id, UserId::new(3));
+        }
+    }
+
+    #[test]
+    fn pin_request_rejects_invalid_custom_id() {
+        for custom_id in [
+            "pin_request",
+            "pin_request:approve",
+            "pin_request:approve:1:2",
+            "pin_request:approve:1:2:3:4",
+            "pin_request:approve:1:x:3",
+            "pin_request:accept:1:2:3",
+            "pin_request_send:1",
+        ] {
+            assert!(PinRequest::parse(custom_id).is_none(), "{custom_id}");
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use serenity::model::id::{GenericChannelId, MessageId, UserId};
+use sqlx::PgPool;
+
+use crate::{app::AppError, features::pin::request::PinRequest};
+
+/// 同じメッセージへのリクエストを再び送信できるまでの分数
+const REQUEST_COOLDOWN_MINUTES: i32 = 10;
+
+/// リクエストの記録を保持する日数
+const REQUEST_RETENTION_DAYS: i32 = 30;
+
+/**
+ピン留めのリクエストの状態と、承認・却下のボタンを表示したメッセージを永続化します。
+
+複数のオーナーに送信したリクエストを一度だけ処理し、全てのボタンを無効にするために使用します。
+*/
+pub(in crate::features::pin) struct PinRequestStore;
+
+impl PinRequestStore {
+    /**
+    リクエストを記録します。
+
+    同じメンバーが同じメッセージを直近にリクエストしている場合は記録せず、`false` を返します。
+    */
+    pub async fn create(database: &PgPool, request: &PinRequest) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "INSERT INTO pin_requests (channel_id, message_id, requester_id) VALUES ($1, $2, $3)
+            ON CONFLICT (channel_id, message_id, requester_id) DO UPDATE SET resolved_by = NULL, created_at = now()
+            WHERE pin_requests.created_at < now() - make_interval(mins => $4)",
+        )
+        .bind(request.channel_id.get() as i64)
+        .bind(request.message_id.get() as i64)
+        .bind(request.requester_id.get() as i64)
+        .bind(REQUEST_COOLDOWN_MINUTES)
+        .execute(database)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /**
+    送信に失敗したリクエストを、すぐに再送信できるよう削除します。
+    */
+    pub async fn delete(database: &PgPool, request: &PinRequest) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM pin_requests WHERE channel_id = $1 AND message_id = $2 AND requester_id = $3")
+            .bind(request.channel_id.get() as i64)
+            .bind(request.message_id.get() as i64)
+            .bind(request.requester_id.get() as i64)
+            .execute(database)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_expired(database: &PgPool) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM pin_requests WHERE created_at < now() - make_interval(days => $1)")
+            .bind(REQUEST_RETENTION_DAYS)
+            .execute(database)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn add_prompt(
+        database: &PgPool,
+        request: &PinRequest,
+        prompt_channel_id: GenericChannelId,
+        prompt_message_id: MessageId,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO pin_request_prompts
+                (prompt_channel_id, prompt_message_id, channel_id, message_id, requester_id)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (prompt_channel_id, prompt_message_id) DO NOTHING",
+        )
+        .bind(prompt_channel_id.get() as i64)
+        .bind(prompt_message_id.get() as i64)
+        .bind(request.channel_id.get() as i64)
+        .bind(request.message_id.get() as i64)
+        .bind(request.requester_id.get() as i64)
+        .execute(database)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn prompts(
+        database: &PgPool,
+        request: &PinRequest,
+    ) -> Result<Vec<(GenericChannelId, MessageId)>, AppError> {
+        let rows: Vec<(i64, i64)> = sqlx::query_as(
+            "SELECT prompt_channel_id, prompt_message_id FROM pin_request_prompts
+            WHERE channel_id = $1 AND message_id = $2 AND requester_id = $3",
+        )
+        .bind(request.channel_id.get() as i64)
+        .bind(request.message_id.get() as i64)
+        .bind(request.requester_id.get() as i64)
+        .fetch_all(database)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(channel_id, message_id)| {
+                (
+                    GenericChannelId::new(channel_id as u64),
+                    MessageId::new(message_id as u64),
+                )
+            })
+            .collect())
+    }
+
+    /**
+    未処理のリクエストを処理済みにします。
+
+    他のオーナーが既に処理していた場合は `false` を返します。
+    記録の無いリクエスト (記録を始める前に送信されたものや、保持期間を過ぎたもの) は未処理として扱います。
+    */
+    pub async fn resolve(database: &PgPool, request: &PinRequest, operator_id: UserId) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "INSERT INTO pin_requests (channel_id, message_id, requester_id, resolved_by) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (channel_id, message_id, requester_id) DO UPDATE SET resolved_by = EXCLUDED.resolved_by
+            WHERE pin_requests.resolved_by IS NULL",
+        )
+        .bind(request.channel_id.get() as i64)
+        .bind(request.message_id.get() as i64)
+        .bind(request.requester_id.get() as i64)
+        .bind(operator_id.get() as i64)
+        .execute(database)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /**
+    処理に失敗したリクエストを、他のオーナーが処理できるよう未処理に戻します。
+    */
+    pub async fn reopen(database: &PgPool, request: &PinRequest) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE pin_requests SET resolved_by = NULL
+            WHERE channel_id = $1 AND message_id = $2 AND requester_id = $3",
+        )
+        .bind(request.channel_id.get() as i64)
+        .bind(request.message_id.get() as i64)
+        .bind(request.requester_id.get() as i64)
+        .execute(database)
+        .await?;
+
+        Ok(())
+    }
+}
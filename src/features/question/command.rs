@@ -1,63 +1,20 @@
+use anyhow::Context as _;
 use poise::CreateReply;
-use serenity::all::{
-    ButtonStyle, CreateActionRow, CreateButton, CreateForumPost, CreateMessage, CreateSelectMenu, CreateSelectMenuKind,
-    CreateSelectMenuOption, ForumEmoji, ForumTag, ForumTagId, MessageBuilder, ReactionType,
-};
-use serenity::builder::CreateComponent;
-use tokio::sync::{RwLock, mpsc};
-use tracing::debug;
-
-use std::borrow::Cow;
-use std::ops::Deref;
-use std::sync::Arc;
-use std::vec;
 
 use crate::app::{AppApplicationContext, AppError, BotDataExt};
-use crate::features::question::create_question_toggle_button;
-use crate::features::question::modal::{BasicQuestionData, DetailedQuestionData};
-use crate::features::question::question_creation_handler::{CustomIds, QuestionCreationHandler};
+use crate::features::question::draft_store::{QuestionDraft, QuestionDraftStore};
+use crate::features::question::form::{create_form, create_resume_prompt, fetch_forum};
 use crate::utils::has_authed_role;
-use anyhow::Context as _;
-
-fn reaction_from_forum_emoji(emoji: &ForumEmoji) -> Option<ReactionType> {
-    match emoji.clone() {
-        ForumEmoji::Id(emoji) => Some(emoji.into()),
-        ForumEmoji::Name(emoji) => Some(emoji.deref().try_into().unwrap()),
-        _ => None,
-    }
-}
 
-fn create_select_menu<'a>(
-    custom_id: impl Into<Cow<'a, str>>,
-    available_tags: &[ForumTag],
-    exclude_tags: &[ForumTagId],
-    selected_tags: &[ForumTagId],
-) -> CreateComponent<'a> {
-    let options = available_tags
+async fn autocomplete_forum(ctx: AppApplicationContext<'_>, partial: &str) -> impl Iterator<Item = String> {
+    let config = &ctx.app_config().await.question;
+    config
+        .forums
         .iter()
-        .filter(|x| !exclude_tags.contains(&x.id))
-        .map(|x| {
-            let opt = CreateSelectMenuOption::new(x.name.clone(), x.id.to_string())
-                .default_selection(selected_tags.contains(&x.id));
-            match &x.emoji {
-                Some(emoji) => opt.emoji(reaction_from_forum_emoji(emoji).unwrap()),
-                None => opt,
-            }
-        })
-        .collect::<Vec<_>>();
-
-    let length = options.len();
-    let select_menu = CreateSelectMenu::new(
-        custom_id,
-        CreateSelectMenuKind::String {
-            options: options.into(),
-        },
-    )
-    .min_values(1)
-    .max_values(length.try_into().unwrap())
-    .placeholder("タグを選択してください");
-
-    CreateComponent::ActionRow(CreateActionRow::select_menu(select_menu))
+        .filter(|forum| forum.name.contains(partial))
+        .map(|forum| forum.name.clone())
+        .collect::<Vec<_>>()
+        .into_iter()
 }
 
 /// Modに関する質問を行うためのフォーラムを作成します。
@@ -69,141 +26,61 @@ fn create_select_menu<'a>(
     member_cooldown = 60,
     check = "has_authed_role"
 )]
-pub async fn question(ctx: AppApplicationContext<'_>) -> Result<(), AppError> {
-    let custom_ids = Arc::new(CustomIds::new(ctx.id()));
-
+pub async fn question(
+    ctx: AppApplicationContext<'_>,
+    #[description = "質問を投稿するフォーラム (省略時は既定のフォーラム)"]
+    #[autocomplete = "autocomplete_forum"]
+    forum: Option<String>,
+) -> Result<(), AppError> {
     ctx.defer_ephemeral().await?;
 
-    let submit_button = CreateButton::new(&custom_ids.submit)
-        .label("質問を送信")
-        .style(ButtonStyle::Success);
-
     let config = &ctx.app_config().await.question;
-    let channel = config
-        .forum_id
-        .to_guild_channel(&ctx, ctx.guild_id())
-        .await
-        .context("Failed to get question forum channel")?;
-
-    let buttons = &[
-        CreateButton::new(&custom_ids.basic)
-            .label("質問の基本情報を入力")
-            .style(ButtonStyle::Primary),
-        CreateButton::new(&custom_ids.detailed)
-            .label("質問の詳細情報を入力")
-            .style(ButtonStyle::Primary),
-        submit_button.clone().disabled(true),
-    ];
-
-    const PROMPT: &str = "ボタンをクリックしてすべての情報を入力してください。\nセレクトボックスからタグを設定してください。\nまた、再度ボタンをクリックすると入力内容を編集することができます。";
-    let message = ctx
-        .send(CreateReply::default().content(PROMPT).components(&[
-            create_select_menu(
-                &custom_ids.select_tag,
-                &channel.available_tags,
-                &[config.solved_tag],
-                &[],
-            ),
-            CreateComponent::ActionRow(CreateActionRow::buttons(&buttons.clone())),
-        ]))
-        .await
-        .context("Failed to send question creation form")?;
-
-    let basic_data = Arc::new(RwLock::new(None::<BasicQuestionData>));
-    let detailed_data = Arc::new(RwLock::new(None::<DetailedQuestionData>));
-    let forum_tag_ids = Arc::new(RwLock::new(Vec::<ForumTagId>::new()));
-
-    let (submit_tx, mut submit_rx) = mpsc::channel::<()>(1);
-    let (inputted_tx, mut inputted_rx) = mpsc::channel::<()>(1);
-
-    {
-        let handler = QuestionCreationHandler {
-            ctx: ctx.serenity_context().clone(),
-            interaction: ctx.interaction.clone(),
-            custom_ids: custom_ids.clone(),
-            basic_data: basic_data.clone(),
-            detailed_data: detailed_data.clone(),
-            forum_tag_ids: forum_tag_ids.clone(),
-            submit_tx,
-            inputted_tx,
-        };
-
-        handler.handle_component_interaction();
-        handler.handle_modal_interaction();
-    }
-
-    if inputted_rx.recv().await.is_none() {
-        debug!("inputted_rx closed");
+    let forum_config = match &forum {
+        Some(name) => config.forums.iter().find(|forum| forum.name == *name),
+        None => config.forums.first(),
+    };
+    let Some(forum_config) = forum_config else {
+        let names = config
+            .forums
+            .iter()
+            .map(|forum| format!("`{}`", forum.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        ctx.send(CreateReply::default().content(format!(
+            "指定された質問フォーラムが見つかりません。次のいずれかを指定してください: {names}"
+        )))
+        .await?;
         return Ok(());
-    }
-    inputted_rx.close();
-
-    const CONFIRM: &str = "情報が入力されました、内容を確認し問題なければ「質問を送信」ボタンをクリックしてください。\n### この機能で作成されるフォームは編集出来ません、間違いが無いように気をつけてください。";
-    message
-        .edit(
-            ctx.into(),
-            CreateReply::default()
-                .content(format!("{PROMPT}\n{CONFIRM}"))
-                .components(&[
-                    create_select_menu(
-                        &custom_ids.select_tag,
-                        &channel.available_tags,
-                        &[config.solved_tag],
-                        &forum_tag_ids.read().await,
-                    ),
-                    CreateComponent::ActionRow(CreateActionRow::buttons(&{
-                        let mut c = buttons.clone();
-                        c[2] = submit_button.clone();
-                        c
-                    })),
-                ]),
-        )
+    };
+
+    // 下書きがある場合は、続きから入力するかどうかを選択させる
+    let database = ctx.database();
+    QuestionDraftStore::delete_expired(&database).await?;
+    let draft = QuestionDraftStore::load(&database, ctx.author().id).await?;
+    let (content, components) = match draft {
+        Some(draft) if !draft.is_empty() => {
+            // フォーラムが指定された場合は、下書きもそのフォーラムに投稿する
+            let resume_forum_id = match (&forum, draft.forum_id) {
+                (None, Some(draft_forum_id)) => draft_forum_id,
+                _ => forum_config.forum_id,
+            };
+            create_resume_prompt(resume_forum_id, forum_config.forum_id)
+        }
+        _ => {
+            let draft = QuestionDraft {
+                forum_id: Some(forum_config.forum_id),
+                ..Default::default()
+            };
+            QuestionDraftStore::save(&database, ctx.author().id, &draft).await?;
+
+            let channel = fetch_forum(ctx.serenity_context(), forum_config, ctx.guild_id()).await?;
+            create_form(&draft, &channel, config, forum_config)
+        }
+    };
+
+    ctx.send(CreateReply::default().content(content).components(components))
         .await
-        .context("Failed to enable question submit button")?;
-
-    if submit_rx.recv().await.is_none() {
-        debug!("submit_rx closed");
-        return Ok(());
-    }
-    submit_rx.close();
-
-    let basic_data = basic_data.read().await.clone().unwrap();
-    let detailed_data = detailed_data.read().await.clone().unwrap();
-    let forum_tag_ids = forum_tag_ids.read().await;
-
-    let msg = MessageBuilder::new()
-        .push_line(&*basic_data.to_string())
-        .push_line(&*detailed_data.to_string())
-        .push("\n質問者: ")
-        .mention(&ctx.interaction.user)
-        .build();
-
-    let forum_channel = channel
-        .id
-        .create_forum_post(
-            ctx.http(),
-            CreateForumPost::new(
-                &basic_data.title,
-                CreateMessage::default()
-                    .content(msg)
-                    .components(&[CreateComponent::ActionRow(CreateActionRow::buttons(&[
-                        create_question_toggle_button(ctx.interaction.user.id, false),
-                    ]))]),
-            )
-            .set_applied_tags(&*forum_tag_ids),
-        )
-        .await
-        .context("Failed to create question forum post")?;
-
-    let msg = MessageBuilder::new()
-        .push_line_safe("質問フォーラムを開始しました。")
-        .mention(&forum_channel)
-        .build();
-
-    message
-        .edit(ctx.into(), CreateReply::default().content(msg).components(vec![]))
-        .await
-        .context("Failed to send question creation result")?;
+        .context("Failed to send question creation form")?;
 
     Ok(())
 }
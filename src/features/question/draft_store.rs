@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, ForumTagId, UserId};
+use sqlx::PgPool;
+
+use crate::{
+    app::{AppError, config::QuestionTemplate},
+    features::question::template::is_modal_inputted,
+};
+
+/**
+質問フォームの入力途中の内容
+
+入力内容はテンプレートのフィールドのIDをキーとして保持します。
+*/
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(in crate::features::question) struct QuestionDraft {
+    /// 投稿先の質問フォーラム (未設定の場合は既定のフォーラム)
+    pub forum_id: Option<ChannelId>,
+    pub values: HashMap<String, String>,
+    pub forum_tag_ids: Vec<ForumTagId>,
+}
+
+impl QuestionDraft {
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.forum_tag_ids.is_empty()
+    }
+
+    pub fn is_complete(&self, template: &QuestionTemplate) -> bool {
+        template
+            .modals
+            .iter()
+            .all(|modal| is_modal_inputted(modal, &self.values))
+            && !self.forum_tag_ids.is_empty()
+    }
+}
+
+/// 下書きを保持する日数 (最後に入力してからこの日数が経過した下書きは削除される)
+const DRAFT_RETENTION_DAYS: i32 = 30;
+
+/**
+質問フォームの下書きをユーザーごとに永続化します。
+
+再起動やタイムアウトで入力内容が失われないよう、入力のたびに保存します。
+*/
+pub(in crate::features::question) struct QuestionDraftStore;
+
+impl QuestionDraftStore {
+    pub async fn load(database: &PgPool, user_id: UserId) -> Result<Option<QuestionDraft>, AppError> {
+        let row: Option<(String,)> = sqlx::query_as("SELECT data FROM question_drafts WHERE user_id = $1")
+            .bind(user_id.get() as i64)
+            .fetch_optional(database)
+            .await?;
+
+        Ok(row.and_then(|(data,)| serenity::json::from_str(&data).ok()))
+    }
+
+    pub async fn save(database: &PgPool, user_id: UserId, draft: &QuestionDraft) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO question_drafts (user_id, data) VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE SET data = EXCLUDED.data, updated_at = now()",
+        )
+        .bind(user_id.get() as i64)
+        .bind(serenity::json::to_string(draft)?)
+        .execute(database)
+        .await?;
+
+        Ok(())
+    }
+
+    /**
+    下書きを削除して取り出します。同時に送信された場合も、取り出せるのはどちらか一方のみです。
+    */
+    pub async fn take(database: &PgPool, user_id: UserId) -> Result<Option<QuestionDraft>, AppError> {
+        let row: Option<(String,)> = sqlx::query_as("DELETE FROM question_drafts WHERE user_id = $1 RETURNING data")
+            .bind(user_id.get() as i64)
+            .fetch_optional(database)
+            .await?;
+
+        Ok(row.and_then(|(data,)| serenity::json::from_str(&data).ok()))
+    }
+
+    /**
+    保持期間を過ぎた下書きを削除します。
+    */
+    pub async fn delete_expired(database: &PgPool) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM question_drafts WHERE updated_at < now() - make_interval(days => $1)")
+            .bind(DRAFT_RETENTION_DAYS)
+            .execute(database)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,397 @@
+use anyhow::Context as _;
+use serenity::{
+    all::{
+        ButtonStyle, CacheHttp, ComponentInteraction, ComponentInteractionDataKind, Context, CreateActionRow,
+        CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage, EditThread, GuildThread,
+        Interaction, ModalInteraction,
+    },
+    builder::{CreateComponent, EditInteractionResponse, EditMessage},
+    model::id::{ForumTagId, GenericChannelId, GuildId, MessageId, UserId},
+};
+
+use crate::{
+    app::{
+        AppError, BotDataExt,
+        config::{QuestionConfig, QuestionForumConfig},
+    },
+    features::question::{
+        form::{create_post_content, create_select_menu, fetch_forum},
+        post_store::{QuestionPost, QuestionPostStore},
+        template::{create_template_modal, parse_template_modal, render_title},
+        toggled_question_name,
+    },
+    utils::create_safe_allowed_mentions,
+};
+
+static QUESTION_EDIT_PREFIX: &str = "question_edit";
+
+const PROMPT: &str = "編集する項目のボタンをクリックしてください。\nセレクトボックスからタグを変更できます。";
+
+/**
+質問の編集の操作
+
+質問のスレッド内で操作されるため、対象のスレッドはインタラクションのチャンネルから取得します。
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditAction {
+    /// 初期メッセージの編集ボタンから編集パネルを開く
+    Open,
+    /// テンプレートの n 番目のモーダルを開く
+    Modal(usize),
+    SelectTag,
+}
+
+impl EditAction {
+    fn custom_id(self) -> String {
+        match self {
+            EditAction::Open => format!("{QUESTION_EDIT_PREFIX}:open"),
+            EditAction::Modal(index) => format!("{QUESTION_EDIT_PREFIX}:modal:{index}"),
+            EditAction::SelectTag => format!("{QUESTION_EDIT_PREFIX}:select_tag"),
+        }
+    }
+
+    fn parse(custom_id: &str) -> Option<Self> {
+        let action = custom_id.strip_prefix(QUESTION_EDIT_PREFIX)?.strip_prefix(':')?;
+        match action {
+            "open" => Some(EditAction::Open),
+            "select_tag" => Some(EditAction::SelectTag),
+            _ => action.strip_prefix("modal:")?.parse().ok().map(EditAction::Modal),
+        }
+    }
+}
+
+/**
+質問の初期メッセージに表示する編集ボタンを作成します。
+*/
+pub(in crate::features::question) fn create_question_edit_button() -> CreateButton<'static> {
+    CreateButton::new(EditAction::Open.custom_id())
+        .label("質問を編集")
+        .style(ButtonStyle::Secondary)
+}
+
+/**
+質問の編集パネルの本文とコンポーネントを作成します。
+*/
+async fn create_edit_panel(
+    ctx: &Context,
+    config: &QuestionConfig,
+    forum_config: &QuestionForumConfig,
+    guild_id: Option<GuildId>,
+    thread: &GuildThread,
+    result: Option<&str>,
+) -> Result<(String, Vec<CreateComponent<'static>>), AppError> {
+    let forum = fetch_forum(ctx, forum_config, guild_id).await?;
+    let template = config.template(forum_config);
+
+    let content = match result {
+        Some(result) => format!("{result}\n{PROMPT}"),
+        None => PROMPT.to_owned(),
+    };
+
+    let buttons = template
+        .modals
+        .iter()
+        .enumerate()
+        .map(|(index, modal)| {
+            CreateButton::new(EditAction::Modal(index).custom_id())
+                .label(modal.button_label.clone())
+                .style(ButtonStyle::Primary)
+        })
+        .collect::<Vec<_>>();
+
+    let mut components = vec![create_select_menu(
+        EditAction::SelectTag.custom_id(),
+        &forum.available_tags,
+        &[forum_config.solved_tag],
+        &thread.applied_tags,
+    )];
+    components.extend(
+        buttons
+            .chunks(5)
+            .map(|row| CreateComponent::ActionRow(CreateActionRow::buttons(row.to_vec()))),
+    );
+
+    Ok((content, components))
+}
+
+/**
+操作されたスレッドと、その質問フォーラムの設定・投稿時の入力内容を取得します。
+
+質問の投稿者以外の操作や、入力内容が保存されていない質問の場合は理由を返します。
+*/
+async fn fetch_question<'a>(
+    ctx: &Context,
+    config: &'a QuestionConfig,
+    channel_id: GenericChannelId,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+) -> Result<Result<(GuildThread, &'a QuestionForumConfig, QuestionPost), &'static str>, AppError> {
+    let thread = channel_id
+        .expect_thread()
+        .to_thread(&ctx, guild_id)
+        .await
+        .context("Failed to get question thread")?;
+
+    let Some(forum_config) = config.forum(thread.parent_id) else {
+        return Ok(Err("このスレッドの質問フォーラムの設定が見つかりません。"));
+    };
+    let Some(post) = QuestionPostStore::load(&ctx.database(), thread.id).await? else {
+        return Ok(Err("この質問は編集できません。"));
+    };
+    if post.author_id != user_id {
+        return Ok(Err("質問者のみが編集できます。"));
+    }
+
+    Ok(Ok((thread, forum_config, post)))
+}
+
+async fn handle_component(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    action: EditAction,
+) -> Result<(), AppError> {
+    let config = &ctx.app_config().await.question;
+    let http = ctx.http();
+
+    let question = fetch_question(
+        ctx,
+        config,
+        interaction.channel_id,
+        interaction.guild_id,
+        interaction.user.id,
+    )
+    .await?;
+    let (thread, forum_config, post) = match question {
+        Ok(question) => question,
+        Err(reason) => {
+            interaction
+                .create_response(
+                    http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().ephemeral(true).content(reason),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    match action {
+        EditAction::Open => {
+            let (content, components) =
+                create_edit_panel(ctx, config, forum_config, interaction.guild_id, &thread, None).await?;
+            interaction
+                .create_response(
+                    http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .ephemeral(true)
+                            .content(content)
+                            .components(components),
+                    ),
+                )
+                .await
+                .context("Failed to open question edit panel")?;
+        }
+        EditAction::Modal(index) => {
+            let template = config.template(forum_config);
+            let Some(modal) = template.modals.get(index) else {
+                return Ok(());
+            };
+
+            interaction
+                .create_response(http, create_template_modal(modal, &post.values, action.custom_id()))
+                .await
+                .context("Failed to open question edit modal")?;
+        }
+        EditAction::SelectTag => {
+            let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind else {
+                return Ok(());
+            };
+
+            interaction
+                .defer(http)
+                .await
+                .context("Failed to defer question tag edit")?;
+
+            // 解決済みのタグは選択肢に含まれないため、付いている場合は残す
+            let mut applied_tags = values
+                .iter()
+                .filter_map(|x| x.parse().ok())
+                .collect::<Vec<ForumTagId>>();
+            if thread.applied_tags.contains(&forum_config.solved_tag) {
+                applied_tags.push(forum_config.solved_tag);
+            }
+
+            let thread = thread
+                .id
+                .edit(http, EditThread::new().applied_tags(applied_tags))
+                .await
+                .context("Failed to edit question thread tags")?;
+
+            let (content, components) = create_edit_panel(
+                ctx,
+                config,
+                forum_config,
+                interaction.guild_id,
+                &thread,
+                Some("タグを更新しました。"),
+            )
+            .await?;
+            interaction
+                .edit_response(
+                    http,
+                    EditInteractionResponse::new().content(content).components(components),
+                )
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_modal(ctx: &Context, interaction: &ModalInteraction, action: EditAction) -> Result<(), AppError> {
+    let EditAction::Modal(index) = action else {
+        return Ok(());
+    };
+
+    let config = &ctx.app_config().await.question;
+    let http = ctx.http();
+
+    interaction.defer(http).await.context("Failed to defer question edit")?;
+
+    let question = fetch_question(
+        ctx,
+        config,
+        interaction.channel_id,
+        interaction.guild_id,
+        interaction.user.id,
+    )
+    .await?;
+    let (thread, forum_config, mut post) = match question {
+        Ok(question) => question,
+        Err(reason) => {
+            interaction
+                .edit_response(http, EditInteractionResponse::new().content(reason).components(vec![]))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let template = config.template(forum_config);
+    // 設定の再読み込みでモーダルが削除されている場合は無視する
+    let Some(modal) = template.modals.get(index) else {
+        return Ok(());
+    };
+
+    post.values.extend(parse_template_modal(modal, &interaction.data));
+
+    // スレッドの初期メッセージのIDはスレッドのIDと同じ
+    let mut start_message = thread
+        .id
+        .widen()
+        .message(ctx, MessageId::new(thread.id.get()))
+        .await
+        .context("Failed to get question start message")?;
+    start_message
+        .edit(
+            ctx,
+            EditMessage::new()
+                .content(create_post_content(&template, &post.values, post.author_id))
+                .allowed_mentions(create_safe_allowed_mentions()),
+        )
+        .await
+        .context("Failed to edit question start message")?;
+
+    // 解決済みの場合は、名前の接頭辞を残す
+    let thread = match render_title(&template, &post.values) {
+        Some(title) => {
+            let solved = thread.applied_tags.contains(&forum_config.solved_tag);
+            thread
+                .id
+                .edit(
+                    http,
+                    EditThread::new().name(toggled_question_name(title, &forum_config.solved_name_prefix, solved)),
+                )
+                .await
+                .context("Failed to edit question thread name")?
+        }
+        None => thread,
+    };
+
+    // 投稿の編集に失敗した場合に保存内容と表示がずれないよう、編集後に保存する
+    QuestionPostStore::save(&ctx.database(), thread.id, &post).await?;
+
+    let (content, components) = create_edit_panel(
+        ctx,
+        config,
+        forum_config,
+        interaction.guild_id,
+        &thread,
+        Some("質問を更新しました。"),
+    )
+    .await?;
+    interaction
+        .edit_response(
+            http,
+            EditInteractionResponse::new().content(content).components(components),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/**
+質問の編集ボタン・セレクトメニュー・モーダルの操作を処理します。
+*/
+pub(in crate::features::question) async fn handle_edit_interaction(
+    ctx: &Context,
+    interaction: &Interaction,
+) -> Result<(), AppError> {
+    match interaction {
+        Interaction::Component(interaction) => {
+            if let Some(action) = EditAction::parse(&interaction.data.custom_id) {
+                handle_component(ctx, interaction, action).await?;
+            }
+        }
+        Interaction::Modal(interaction) => {
+            if let Some(action) = EditAction::parse(&interaction.data.custom_id) {
+                handle_modal(ctx, interaction, action).await?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_action_round_trips_custom_id() {
+        for action in [
+            EditAction::Open,
+            EditAction::Modal(0),
+            EditAction::Modal(3),
+            EditAction::SelectTag,
+        ] {
+            assert_eq!(EditAction::parse(&action.custom_id()), Some(action));
+        }
+    }
+
+    #[test]
+    fn edit_action_rejects_invalid_custom_id() {
+        for custom_id in [
+            "question_edit",
+            "question_edit:",
+            "question_edit:modal:",
+            "question_edit:modal:x",
+            "question_edit:open:1",
+            "question_form:submit",
+        ] {
+            assert_eq!(EditAction::parse(custom_id), None, "{custom_id}");
+        }
+    }
+}
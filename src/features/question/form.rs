@@ -0,0 +1,534 @@
+use std::{borrow::Cow, collections::HashMap, ops::Deref};
+
+use anyhow::{Context as _, anyhow};
+use serenity::{
+    all::{
+        ButtonStyle, CacheHttp, ComponentInteraction, ComponentInteractionDataKind, Context, CreateActionRow,
+        CreateButton, CreateForumPost, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
+        CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, ForumEmoji, ForumTag, ForumTagId, GuildChannel,
+        GuildThread, Interaction, MessageBuilder, ModalInteraction, ReactionType, User, UserId,
+    },
+    builder::{CreateComponent, EditInteractionResponse},
+    model::id::{ChannelId, GuildId},
+};
+
+use crate::{
+    app::{
+        AppError, BotDataExt,
+        config::{QuestionConfig, QuestionForumConfig, QuestionTemplate},
+    },
+    features::question::{
+        create_question_buttons,
+        draft_store::{QuestionDraft, QuestionDraftStore},
+        post_store::{QuestionPost, QuestionPostStore},
+        template::{create_template_modal, is_modal_inputted, parse_template_modal, render_output, render_title},
+    },
+};
+use tracing::warn;
+
+static QUESTION_FORM_PREFIX: &str = "question_form";
+
+const PROMPT: &str = "ボタンをクリックしてすべての情報を入力してください。\nセレクトボックスからタグを設定してください。\nまた、再度ボタンをクリックすると入力内容を編集することができます。\n入力内容は下書きとして保存され、`/question` から再開できます。";
+const CONFIRM: &str = "情報が入力されました、内容を確認し問題なければ「質問を送信」ボタンをクリックしてください。\n送信後も質問の初期メッセージの「質問を編集」ボタンから内容を編集できます。";
+const RESUME_PROMPT: &str = "保存された下書きがあります。続きから入力しますか？";
+
+/**
+質問フォームの操作
+
+ボタン・セレクトメニュー・モーダルのカスタムIDに埋め込み、起動中のコマンドに依存せずに処理します。
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormAction {
+    /// テンプレートの n 番目のモーダルを開く
+    Modal(usize),
+    SelectTag,
+    Submit,
+    /// 下書きを指定したフォーラムへの投稿として再開する
+    Resume(ChannelId),
+    /// 下書きを破棄し、指定したフォーラムの新しいフォームを開く
+    Discard(ChannelId),
+}
+
+impl FormAction {
+    fn custom_id(self) -> String {
+        match self {
+            FormAction::Modal(index) => format!("{QUESTION_FORM_PREFIX}:modal:{index}"),
+            FormAction::SelectTag => format!("{QUESTION_FORM_PREFIX}:select_tag"),
+            FormAction::Submit => format!("{QUESTION_FORM_PREFIX}:submit"),
+            FormAction::Resume(forum_id) => format!("{QUESTION_FORM_PREFIX}:resume:{forum_id}"),
+            FormAction::Discard(forum_id) => format!("{QUESTION_FORM_PREFIX}:discard:{forum_id}"),
+        }
+    }
+
+    fn parse(custom_id: &str) -> Option<Self> {
+        let action = custom_id.strip_prefix(QUESTION_FORM_PREFIX)?.strip_prefix(':')?;
+        let (name, argument) = match action.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (action, None),
+        };
+
+        match (name, argument) {
+            ("modal", Some(index)) => index.parse().ok().map(FormAction::Modal),
+            ("select_tag", None) => Some(FormAction::SelectTag),
+            ("submit", None) => Some(FormAction::Submit),
+            ("resume", Some(forum_id)) => forum_id.parse().ok().map(FormAction::Resume),
+            ("discard", Some(forum_id)) => forum_id.parse().ok().map(FormAction::Discard),
+            _ => None,
+        }
+    }
+}
+
+fn reaction_from_forum_emoji(emoji: &ForumEmoji) -> Option<ReactionType> {
+    match emoji.clone() {
+        ForumEmoji::Id(emoji) => Some(emoji.into()),
+        ForumEmoji::Name(emoji) => Some(emoji.deref().try_into().unwrap()),
+        _ => None,
+    }
+}
+
+pub(in crate::features::question) fn create_select_menu<'a>(
+    custom_id: impl Into<Cow<'a, str>>,
+    available_tags: &[ForumTag],
+    exclude_tags: &[ForumTagId],
+    selected_tags: &[ForumTagId],
+) -> CreateComponent<'a> {
+    let options = available_tags
+        .iter()
+        .filter(|x| !exclude_tags.contains(&x.id))
+        .map(|x| {
+            let opt = CreateSelectMenuOption::new(x.name.clone(), x.id.to_string())
+                .default_selection(selected_tags.contains(&x.id));
+            match &x.emoji {
+                Some(emoji) => opt.emoji(reaction_from_forum_emoji(emoji).unwrap()),
+                None => opt,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let length = options.len();
+    let select_menu = CreateSelectMenu::new(
+        custom_id,
+        CreateSelectMenuKind::String {
+            options: options.into(),
+        },
+    )
+    .min_values(1)
+    .max_values(length.try_into().unwrap())
+    .placeholder("タグを選択してください");
+
+    CreateComponent::ActionRow(CreateActionRow::select_menu(select_menu))
+}
+
+fn input_status(inputted: bool) -> &'static str {
+    if inputted { "入力済み" } else { "未入力" }
+}
+
+/**
+下書きの内容を反映した質問フォームの本文とコンポーネントを作成します。
+*/
+pub(in crate::features::question) fn create_form(
+    draft: &QuestionDraft,
+    forum: &GuildChannel,
+    config: &QuestionConfig,
+    forum_config: &QuestionForumConfig,
+) -> (String, Vec<CreateComponent<'static>>) {
+    let template = config.template(forum_config);
+
+    let mut content = format!("{PROMPT}\n- 投稿先: {}", forum_config.name);
+    for modal in &template.modals {
+        content = format!(
+            "{content}\n- {}: {}",
+            modal.button_label,
+            input_status(is_modal_inputted(modal, &draft.values))
+        );
+    }
+    content = format!("{content}\n- タグ: {}", input_status(!draft.forum_tag_ids.is_empty()));
+    if draft.is_complete(&template) {
+        content = format!("{content}\n{CONFIRM}");
+    }
+
+    // 1行に配置できるボタンは5つまでのため、送信ボタンと合わせて行を分ける
+    let buttons = template
+        .modals
+        .iter()
+        .enumerate()
+        .map(|(index, modal)| {
+            CreateButton::new(FormAction::Modal(index).custom_id())
+                .label(modal.button_label.clone())
+                .style(ButtonStyle::Primary)
+        })
+        .chain([CreateButton::new(FormAction::Submit.custom_id())
+            .label("質問を送信")
+            .style(ButtonStyle::Success)
+            .disabled(!draft.is_complete(&template))])
+        .collect::<Vec<_>>();
+
+    let mut components = vec![create_select_menu(
+        FormAction::SelectTag.custom_id(),
+        &forum.available_tags,
+        &[forum_config.solved_tag],
+        &draft.forum_tag_ids,
+    )];
+    components.extend(
+        buttons
+            .chunks(5)
+            .map(|row| CreateComponent::ActionRow(CreateActionRow::buttons(row.to_vec()))),
+    );
+
+    (content, components)
+}
+
+/**
+下書きを再開するか破棄するかを選択させる本文とコンポーネントを作成します。
+
+再開する場合は `resume_forum_id` に、新しく作成する場合は `new_forum_id` に投稿します。
+*/
+pub(in crate::features::question) fn create_resume_prompt(
+    resume_forum_id: ChannelId,
+    new_forum_id: ChannelId,
+) -> (String, Vec<CreateComponent<'static>>) {
+    let components = vec![CreateComponent::ActionRow(CreateActionRow::buttons(vec![
+        CreateButton::new(FormAction::Resume(resume_forum_id).custom_id())
+            .label("下書きを再開")
+            .style(ButtonStyle::Primary),
+        CreateButton::new(FormAction::Discard(new_forum_id).custom_id())
+            .label("新しく作成")
+            .style(ButtonStyle::Secondary),
+    ]))];
+
+    (RESUME_PROMPT.to_owned(), components)
+}
+
+pub(in crate::features::question) fn update_message(
+    (content, components): (String, Vec<CreateComponent<'static>>),
+) -> CreateInteractionResponse<'static> {
+    CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .components(components),
+    )
+}
+
+/**
+下書きの投稿先の質問フォーラムの設定を返します。
+*/
+pub(in crate::features::question) fn draft_forum<'a>(
+    config: &'a QuestionConfig,
+    draft: &QuestionDraft,
+) -> Result<&'a QuestionForumConfig, AppError> {
+    config
+        .forum_or_default(draft.forum_id)
+        .context("No question forum is configured")
+}
+
+pub(in crate::features::question) async fn fetch_forum(
+    ctx: &Context,
+    forum_config: &QuestionForumConfig,
+    guild_id: Option<GuildId>,
+) -> Result<GuildChannel, AppError> {
+    forum_config
+        .forum_id
+        .to_guild_channel(ctx, guild_id)
+        .await
+        .context("Failed to get question forum channel")
+}
+
+/**
+質問の初期メッセージの本文を作成します。
+*/
+pub(in crate::features::question) fn create_post_content(
+    template: &QuestionTemplate,
+    values: &HashMap<String, String>,
+    author_id: UserId,
+) -> String {
+    MessageBuilder::new()
+        .push_line(&*render_output(template, values))
+        .push("\n質問者: ")
+        .mention(&author_id)
+        .build()
+}
+
+async fn create_question_post(
+    ctx: &Context,
+    forum: &GuildChannel,
+    template: &QuestionTemplate,
+    draft: &QuestionDraft,
+    author: &User,
+) -> Result<GuildThread, AppError> {
+    let Some(title) = render_title(template, &draft.values) else {
+        return Err(anyhow!("Question draft has no title"));
+    };
+
+    let msg = create_post_content(template, &draft.values, author.id);
+
+    forum
+        .id
+        .create_forum_post(
+            ctx.http(),
+            CreateForumPost::new(
+                title,
+                CreateMessage::default()
+                    .content(msg)
+                    .components(vec![create_question_buttons(author.id, false, true)]),
+            )
+            .set_applied_tags(&*draft.forum_tag_ids),
+        )
+        .await
+        .context("Failed to create question forum post")
+}
+
+async fn submit(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    config: &QuestionConfig,
+    draft: &QuestionDraft,
+) -> Result<(), AppError> {
+    let user = &interaction.user;
+    let forum_config = draft_forum(config, draft)?;
+    let forum = fetch_forum(ctx, forum_config, interaction.guild_id).await?;
+    let template = config.template(forum_config);
+
+    if !draft.is_complete(&template) {
+        interaction
+            .create_response(
+                ctx.http(),
+                update_message(create_form(draft, &forum, config, forum_config)),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    interaction
+        .defer(ctx.http())
+        .await
+        .context("Failed to defer question submission")?;
+
+    let result = post_draft(ctx, &forum, &template, user).await;
+    let msg = match &result {
+        Ok(Some(forum_channel)) => MessageBuilder::new()
+            .push_line_safe("質問フォーラムを開始しました。")
+            .mention(forum_channel)
+            .build(),
+        Ok(None) => "この質問は既に送信されています。".to_owned(),
+        Err(_) => "質問を送信できませんでした。時間をおいて再度お試しください。".to_owned(),
+    };
+
+    interaction
+        .edit_response(
+            ctx.http(),
+            EditInteractionResponse::new().content(msg).components(vec![]),
+        )
+        .await
+        .context("Failed to send question creation result")?;
+
+    result.map(|_| ())
+}
+
+/**
+下書きを取り出して質問を投稿します。
+
+ボタンが連続で押された場合に重複して投稿しないよう、下書きを先に取り出します。
+既に取り出されていた場合は `None` を、投稿に失敗した場合は下書きを戻してエラーを返します。
+編集用の回答の保存は投稿の作成後に行い、失敗しても投稿は成功として扱います。
+*/
+async fn post_draft(
+    ctx: &Context,
+    forum: &GuildChannel,
+    template: &QuestionTemplate,
+    user: &User,
+) -> Result<Option<GuildThread>, AppError> {
+    let database = ctx.database();
+    let Some(draft) = QuestionDraftStore::take(&database, user.id).await? else {
+        return Ok(None);
+    };
+
+    let forum_channel = match create_question_post(ctx, forum, template, &draft, user).await {
+        Ok(forum_channel) => forum_channel,
+        Err(error) => {
+            QuestionDraftStore::save(&database, user.id, &draft).await?;
+            return Err(error);
+        }
+    };
+    // 投稿は作成済みのため、保存に失敗しても投稿自体は成功として扱う (編集できなくなるだけ)
+    if let Err(error) = QuestionPostStore::save(
+        &database,
+        forum_channel.id,
+        &QuestionPost {
+            author_id: user.id,
+            values: draft.values,
+        },
+    )
+    .await
+    {
+        warn!("Failed to save question post {}: {error:#}", forum_channel.id);
+    }
+
+    Ok(Some(forum_channel))
+}
+
+async fn handle_component(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    action: FormAction,
+) -> Result<(), AppError> {
+    let database = ctx.database();
+    let user_id = interaction.user.id;
+    let config = &ctx.app_config().await.question;
+    let http = ctx.http();
+
+    let draft = QuestionDraftStore::load(&database, user_id).await?.unwrap_or_default();
+
+    // モーダルを開く際の応答を遅らせないよう、フォーラムの取得はフォームを更新する場合のみ行う
+    match action {
+        FormAction::Modal(index) => {
+            let template = config.template(draft_forum(config, &draft)?);
+            let Some(modal) = template.modals.get(index) else {
+                return Ok(());
+            };
+
+            interaction
+                .create_response(http, create_template_modal(modal, &draft.values, action.custom_id()))
+                .await
+                .context("Failed to open question modal")?;
+        }
+        FormAction::SelectTag => {
+            let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind else {
+                return Ok(());
+            };
+
+            let mut draft = draft;
+            draft.forum_tag_ids = values.iter().filter_map(|x| x.parse().ok()).collect();
+            QuestionDraftStore::save(&database, user_id, &draft).await?;
+
+            let forum_config = draft_forum(config, &draft)?;
+            let forum = fetch_forum(ctx, forum_config, interaction.guild_id).await?;
+            interaction
+                .create_response(http, update_message(create_form(&draft, &forum, config, forum_config)))
+                .await?;
+        }
+        FormAction::Submit => submit(ctx, interaction, config, &draft).await?,
+        FormAction::Resume(forum_id) => {
+            // 別のフォーラムが指定された場合は、そのフォーラムにないタグを外して投稿先を変更する
+            let mut draft = draft;
+            if draft.forum_id != Some(forum_id) {
+                draft.forum_id = Some(forum_id);
+                draft.forum_tag_ids.clear();
+                QuestionDraftStore::save(&database, user_id, &draft).await?;
+            }
+
+            let forum_config = draft_forum(config, &draft)?;
+            let forum = fetch_forum(ctx, forum_config, interaction.guild_id).await?;
+            interaction
+                .create_response(http, update_message(create_form(&draft, &forum, config, forum_config)))
+                .await?;
+        }
+        FormAction::Discard(forum_id) => {
+            let draft = QuestionDraft {
+                forum_id: Some(forum_id),
+                ..Default::default()
+            };
+            QuestionDraftStore::save(&database, user_id, &draft).await?;
+
+            let forum_config = draft_forum(config, &draft)?;
+            let forum = fetch_forum(ctx, forum_config, interaction.guild_id).await?;
+            interaction
+                .create_response(http, update_message(create_form(&draft, &forum, config, forum_config)))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_modal(ctx: &Context, interaction: &ModalInteraction, action: FormAction) -> Result<(), AppError> {
+    let FormAction::Modal(index) = action else {
+        return Ok(());
+    };
+
+    let database = ctx.database();
+    let user_id = interaction.user.id;
+    let config = &ctx.app_config().await.question;
+    let mut draft = QuestionDraftStore::load(&database, user_id).await?.unwrap_or_default();
+    let forum_config = draft_forum(config, &draft)?;
+    let template = config.template(forum_config);
+    // 設定の再読み込みでモーダルが削除されている場合は無視する
+    let Some(modal) = template.modals.get(index) else {
+        return Ok(());
+    };
+
+    draft.values.extend(parse_template_modal(modal, &interaction.data));
+    QuestionDraftStore::save(&database, user_id, &draft).await?;
+
+    let forum = fetch_forum(ctx, forum_config, interaction.guild_id).await?;
+    interaction
+        .create_response(
+            ctx.http(),
+            update_message(create_form(&draft, &forum, config, forum_config)),
+        )
+        .await
+        .context("Failed to update question form")?;
+
+    Ok(())
+}
+
+/**
+質問フォームのボタン・セレクトメニュー・モーダルの操作を処理します。
+
+状態は全て下書きとして保存されているため、再起動後に操作された場合も処理できます。
+*/
+pub(in crate::features::question) async fn handle_form_interaction(
+    ctx: &Context,
+    interaction: &Interaction,
+) -> Result<(), AppError> {
+    match interaction {
+        Interaction::Component(interaction) => {
+            if let Some(action) = FormAction::parse(&interaction.data.custom_id) {
+                handle_component(ctx, interaction, action).await?;
+            }
+        }
+        Interaction::Modal(interaction) => {
+            if let Some(action) = FormAction::parse(&interaction.data.custom_id) {
+                handle_modal(ctx, interaction, action).await?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn form_action_round_trips_custom_id() {
+        for action in [
+            FormAction::Modal(0),
+            FormAction::Modal(18),
+            FormAction::SelectTag,
+            FormAction::Submit,
+            FormAction::Resume(ChannelId::new(1234)),
+            FormAction::Discard(ChannelId::new(5678)),
+        ] {
+            assert_eq!(FormAction::parse(&action.custom_id()), Some(action));
+        }
+    }
+
+    #[test]
+    fn form_action_rejects_invalid_custom_id() {
+        for custom_id in [
+            "question_form",
+            "question_form:",
+            "question_form:modal",
+            "question_form:modal:x",
+            "question_form:submit:1",
+            "question_form:resume",
+            "question_form:unknown",
+            "question_edit:open",
+            "close_question_forum:1",
+        ] {
+            assert_eq!(FormAction::parse(custom_id), None, "{custom_id}");
+        }
+    }
+}
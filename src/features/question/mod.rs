@@ -1,6 +1,9 @@
 mod command;
-mod modal;
-mod question_creation_handler;
+mod draft_store;
+mod edit;
+mod form;
+mod post_store;
+mod template;
 
 use std::{str::FromStr, time::Duration};
 
@@ -18,7 +21,17 @@ use serenity::{
 use tracing::warn;
 use valine_bot_macros::event_handler;
 
-use crate::app::{AppError, BotDataExt};
+use crate::{
+    app::{AppError, BotDataExt},
+    features::{
+        pin::post_solved_pin_digest,
+        question::{
+            edit::{create_question_edit_button, handle_edit_interaction},
+            form::handle_form_interaction,
+            post_store::QuestionPostStore,
+        },
+    },
+};
 
 pub static QUESTION_CLOSE_PREFIX: &str = "close_question_forum";
 const QUESTION_THREAD_EDIT_TIMEOUT: Duration = Duration::from_secs(10);
@@ -61,6 +74,19 @@ pub fn create_question_toggle_button(author_id: UserId, solved: bool) -> CreateB
     }
 }
 
+/**
+質問の初期メッセージに表示する、解決済みの切り替えボタンと編集ボタンを作成します。
+
+回答が保存されていない質問 (編集機能の追加前の質問など) は編集できないため、切り替えボタンのみを表示します。
+*/
+fn create_question_buttons(author_id: UserId, solved: bool, editable: bool) -> CreateComponent<'static> {
+    let mut buttons = vec![create_question_toggle_button(author_id, solved)];
+    if editable {
+        buttons.push(create_question_edit_button());
+    }
+    CreateComponent::ActionRow(CreateActionRow::buttons(buttons))
+}
+
 async fn handle_interaction_create(ctx: &Context, interaction: &Interaction) -> Result<(), AppError> {
     let Interaction::Component(interaction) = interaction else {
         return Ok(());
@@ -88,6 +114,17 @@ async fn handle_interaction_create(ctx: &Context, interaction: &Interaction) ->
         .await
         .context("Failed to get question thread")?;
 
+    // 解決済みのタグや名前の接頭辞は、スレッドの親の質問フォーラムの設定を使う
+    let Some(config) = config.forum(thread.parent_id) else {
+        interaction
+            .edit_response(
+                ctx.http(),
+                EditInteractionResponse::new().content("このスレッドの質問フォーラムの設定が見つかりません。"),
+            )
+            .await?;
+        return Ok(());
+    };
+
     let next_solved = !thread.applied_tags.contains(&config.solved_tag);
 
     let edit_thread = EditThread::new()
@@ -125,13 +162,18 @@ async fn handle_interaction_create(ctx: &Context, interaction: &Interaction) ->
         }
     }
 
+    let editable = match QuestionPostStore::load(&ctx.database(), thread.id).await {
+        Ok(post) => post.is_some(),
+        Err(error) => {
+            warn!("Failed to load question post {}: {error:#}", thread.id);
+            false
+        }
+    };
     let mut start_message = (*interaction.message).clone();
     start_message
         .edit(
             &ctx,
-            EditMessage::new().components(&[CreateComponent::ActionRow(CreateActionRow::buttons(&[
-                create_question_toggle_button(author_id, next_solved),
-            ]))]),
+            EditMessage::new().components(vec![create_question_buttons(author_id, next_solved, editable)]),
         )
         .await
         .context("Failed to update question start message button")?;
@@ -147,6 +189,11 @@ async fn handle_interaction_create(ctx: &Context, interaction: &Interaction) ->
         )
         .await?;
 
+    // 長い質問スレッドを振り返れるよう、解決済みにしたときはピン留めの一覧を投稿する
+    if next_solved && let Err(error) = post_solved_pin_digest(ctx, &thread).await {
+        warn!("Failed to post pin digest to solved question {}: {error:#}", thread.id);
+    }
+
     Ok(())
 }
 
@@ -154,6 +201,8 @@ async fn handle_interaction_create(ctx: &Context, interaction: &Interaction) ->
 pub async fn handle_question_event(ctx: &Context, event: &FullEvent) -> Result<(), AppError> {
     if let FullEvent::InteractionCreate { interaction, .. } = event {
         handle_interaction_create(ctx, interaction).await?;
+        handle_form_interaction(ctx, interaction).await?;
+        handle_edit_interaction(ctx, interaction).await?;
     }
 
     Ok(())
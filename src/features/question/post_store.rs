@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use serenity::model::id::{ThreadId, UserId};
+use sqlx::PgPool;
+
+use crate::app::AppError;
+
+/**
+投稿された質問のフォームの入力内容
+*/
+#[derive(Debug, Clone)]
+pub(in crate::features::question) struct QuestionPost {
+    pub author_id: UserId,
+    pub values: HashMap<String, String>,
+}
+
+/**
+投稿された質問の入力内容をスレッドごとに永続化します。
+
+質問の編集時に、モーダルへ投稿時の入力内容を埋めるために使用します。
+*/
+pub(in crate::features::question) struct QuestionPostStore;
+
+impl QuestionPostStore {
+    pub async fn load(database: &PgPool, thread_id: ThreadId) -> Result<Option<QuestionPost>, AppError> {
+        let row: Option<(i64, String)> =
+            sqlx::query_as("SELECT author_id, data FROM question_posts WHERE thread_id = $1")
+                .bind(thread_id.get() as i64)
+                .fetch_optional(database)
+                .await?;
+
+        Ok(row.and_then(|(author_id, data)| {
+            Some(QuestionPost {
+                author_id: UserId::new(author_id as u64),
+                values: serenity::json::from_str(&data).ok()?,
+            })
+        }))
+    }
+
+    pub async fn save(database: &PgPool, thread_id: ThreadId, post: &QuestionPost) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO question_posts (thread_id, author_id, data) VALUES ($1, $2, $3)
+            ON CONFLICT (thread_id) DO UPDATE SET data = EXCLUDED.data, updated_at = now()",
+        )
+        .bind(thread_id.get() as i64)
+        .bind(post.author_id.get() as i64)
+        .bind(serenity::json::to_string(&post.values)?)
+        .execute(database)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use serenity::{
+    all::{CreateInputText, InputTextStyle, ModalInteractionData},
+    builder::{CreateInteractionResponse, CreateLabel, CreateModalComponent},
+    model::application::{LabelComponent, ModalComponent},
+};
+
+use crate::{
+    app::config::{QuestionModalTemplate, QuestionTemplate},
+    utils::create_model,
+};
+
+/**
+テンプレートのモーダルを、入力済みの内容 (未入力の場合は初期値) を埋めて作成します。
+*/
+pub(in crate::features::question) fn create_template_modal(
+    modal: &QuestionModalTemplate,
+    values: &HashMap<String, String>,
+    custom_id: String,
+) -> CreateInteractionResponse<'static> {
+    let components = modal
+        .fields
+        .iter()
+        .map(|field| {
+            let style = if field.paragraph {
+                InputTextStyle::Paragraph
+            } else {
+                InputTextStyle::Short
+            };
+            let mut input = CreateInputText::new(style, field.id.clone()).required(field.required);
+
+            if let Some(placeholder) = &field.placeholder {
+                input = input.placeholder(placeholder.clone());
+            }
+            if let Some(min_length) = field.min_length {
+                input = input.min_length(min_length);
+            }
+            if let Some(max_length) = field.max_length {
+                input = input.max_length(max_length);
+            }
+            if let Some(value) = values.get(&field.id).or(field.default.as_ref()) {
+                input = input.value(value.clone());
+            }
+
+            CreateModalComponent::Label(CreateLabel::input_text(field.label.clone(), input))
+        })
+        .collect::<Vec<_>>();
+
+    create_model(custom_id, modal.title.clone(), components)
+}
+
+/**
+モーダルの入力内容をフィールドのIDごとに取り出します。未入力の任意のフィールドは空文字列になります。
+*/
+pub(in crate::features::question) fn parse_template_modal(
+    modal: &QuestionModalTemplate,
+    data: &ModalInteractionData,
+) -> HashMap<String, String> {
+    let mut inputs = data
+        .components
+        .iter()
+        .filter_map(|component| match component {
+            ModalComponent::Label(label) => match &label.component {
+                LabelComponent::InputText(text) => Some((text.custom_id.to_string(), text.value.to_string())),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    modal
+        .fields
+        .iter()
+        .map(|field| (field.id.clone(), inputs.remove(&field.id).unwrap_or_default()))
+        .collect()
+}
+
+/**
+モーダルの全てのフィールドが入力済みかどうかを判定します。
+*/
+pub(in crate::features::question) fn is_modal_inputted(
+    modal: &QuestionModalTemplate,
+    values: &HashMap<String, String>,
+) -> bool {
+    modal.fields.iter().all(|field| values.contains_key(&field.id))
+}
+
+/**
+テンプレートの `output` の `{フィールドID}` を入力内容に置き換えます。
+
+入力内容に `{フィールドID}` が含まれていても置き換えないよう、`output` を一度だけ走査します。
+*/
+pub(in crate::features::question) fn render_output(
+    template: &QuestionTemplate,
+    values: &HashMap<String, String>,
+) -> String {
+    let replacements = template
+        .modals
+        .iter()
+        .flat_map(|modal| &modal.fields)
+        .map(|field| {
+            let value = values
+                .get(&field.id)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .unwrap_or(&field.empty_text);
+            (field.id.as_str(), value)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut output = String::with_capacity(template.output.len());
+    let mut rest = template.output.as_str();
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let replacement = rest
+            .find('}')
+            .and_then(|end| replacements.get(&rest[1..end]).map(|value| (end, value)));
+        match replacement {
+            Some((end, value)) => {
+                output.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/**
+スレッドのタイトルに使用する入力内容を返します。
+*/
+pub(in crate::features::question) fn render_title<'a>(
+    template: &QuestionTemplate,
+    values: &'a HashMap<String, String>,
+) -> Option<&'a str> {
+    values
+        .get(&template.title_field)
+        .map(|title| title.trim())
+        .filter(|title| !title.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> QuestionTemplate {
+        toml::from_str(
+            r#"
+            title_field = "title"
+            output = "内容: {content}\nログ: {logs}"
+
+            [[modals]]
+            title = "質問"
+            button_label = "質問を入力"
+            fields = [
+                { id = "title", label = "タイトル", max_length = 100 },
+                { id = "content", label = "内容", paragraph = true },
+                { id = "logs", label = "ログ", required = false, empty_text = "(ログなし)" },
+            ]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn values(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn render_output_replaces_fields() {
+        let values = values(&[("content", " 動きません \n"), ("logs", "error")]);
+        assert_eq!(render_output(&template(), &values), "内容: 動きません\nログ: error");
+    }
+
+    #[test]
+    fn render_output_uses_empty_text_for_blank_fields() {
+        let values = values(&[("content", "動きません"), ("logs", "  ")]);
+        assert_eq!(
+            render_output(&template(), &values),
+            "内容: 動きません\nログ: (ログなし)"
+        );
+    }
+
+    #[test]
+    fn render_output_does_not_substitute_inside_values() {
+        let values = values(&[("content", "{logs} と {title}"), ("logs", "error")]);
+        assert_eq!(
+            render_output(&template(), &values),
+            "内容: {logs} と {title}\nログ: error"
+        );
+    }
+
+    #[test]
+    fn render_output_keeps_unknown_placeholders() {
+        let mut template = template();
+        template.output = "{unknown} {content} { {".to_owned();
+        let values = values(&[("content", "本文")]);
+        assert_eq!(render_output(&template, &values), "{unknown} 本文 { {");
+    }
+
+    #[test]
+    fn render_title_trims_and_skips_blank_titles() {
+        assert_eq!(
+            render_title(&template(), &values(&[("title", " 起動しない ")])),
+            Some("起動しない")
+        );
+        assert_eq!(render_title(&template(), &values(&[("title", "  ")])), None);
+        assert_eq!(render_title(&template(), &values(&[])), None);
+    }
+}
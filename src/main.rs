@@ -14,9 +14,9 @@ use tracing::error;
 use tracing_subscriber::EnvFilter;
 
 use crate::{
-    app::{AppError, BotData, MainEventHandler, config::AppConfig, handle_event_error, on_error},
+    app::{AppError, BotData, MainEventHandler, config::AppConfig, connect_database, handle_event_error, on_error},
     core::{create_client, install_signal_handler},
-    features::{commands, event_handlers},
+    features::{commands, event_handlers, register_extensions},
 };
 
 #[derive(Clone, Debug, Bpaf)]
@@ -50,6 +50,8 @@ async fn main() -> Result<(), AppError> {
         return Ok(());
     }
 
+    let database = connect_database().await?;
+
     let framework = Framework::builder()
         .options(FrameworkOptions {
             prefix_options: PrefixFrameworkOptions {
@@ -66,12 +68,13 @@ async fn main() -> Result<(), AppError> {
         .build();
 
     let intents = GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS
         | GatewayIntents::GUILDS
         | GatewayIntents::GUILD_MEMBERS
         | GatewayIntents::MESSAGE_CONTENT;
 
     let mut settings = CacheSettings::default();
-    settings.max_messages = usize::MAX;
+    settings.max_messages = config.message_cache.max_messages_per_channel;
 
     let mut client = create_client(
         config.bot.token.clone(),
@@ -82,7 +85,7 @@ async fn main() -> Result<(), AppError> {
     )
     .framework(Box::new(framework))
     .cache_settings(settings)
-    .data(Arc::new(BotData::new(config)))
+    .data(Arc::new(register_extensions(BotData::new(config, database))))
     .await
     .context("Failed to create Discord client")?;
 
@@ -13,9 +13,9 @@ use serenity::{
         CreateComponent, CreateInteractionResponse, CreateInteractionResponseMessage, CreateModal, CreateModalComponent,
     },
     model::{
-        channel::{ChannelType, GuildThread, MessageFlags},
+        channel::{ChannelType, GenericGuildChannelRef, GuildThread, MessageFlags},
         guild::Member,
-        id::GuildId,
+        id::{GenericChannelId, GuildId},
     },
 };
 
@@ -127,6 +127,41 @@ pub async fn send_message<'a>(ctx: &Context, channel_id: &ChannelId, builder: Cr
     channel_id.widen().send_message(ctx.http(), builder).await
 }
 
+/**
+指定されたチャンネル自身と、その親チャンネル・カテゴリのIDを近い順にキャッシュから取得します。
+
+キャッシュに無いチャンネルに到達した時点で打ち切ります。
+ */
+pub fn channel_ancestor_ids(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    channel_id: GenericChannelId,
+) -> Vec<GenericChannelId> {
+    let mut ids = vec![channel_id];
+    let Some(guild) = guild_id.and_then(|id| id.to_guild_cached(&ctx.cache)) else {
+        return ids;
+    };
+
+    let mut current = channel_id;
+    loop {
+        let parent_id = match guild.channel(current) {
+            Some(GenericGuildChannelRef::Thread(thread)) => Some(thread.parent_id),
+            Some(GenericGuildChannelRef::Channel(channel)) => channel.parent_id,
+            _ => None,
+        };
+
+        match parent_id {
+            Some(parent_id) if !ids.contains(&parent_id.widen()) => {
+                current = parent_id.widen();
+                ids.push(current);
+            }
+            _ => break,
+        }
+    }
+
+    ids
+}
+
 /**
 指定されたチャンネルのアーカイブされたパブリックスレッドをすべて取得します。
  */